@@ -26,7 +26,7 @@ fn main() {
                 }),
                 ..default()
             }),
-            ImageExportPlugin,
+            ImageExportPlugin::disk("."),
         ))
         .insert_resource(AmbientLight {
             color: Color::WHITE,
@@ -92,7 +92,7 @@ fn setup(
 
     commands.spawn((
         PbrBundle {
-            mesh: meshes.add(Mesh::try_from(shape::Cube::default()).unwrap()),
+            mesh: meshes.add(Mesh::from(shape::Cube::default())),
             material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
             ..default()
         },
@@ -9,8 +9,11 @@ use bevy::{
     window::WindowResolution,
     winit::WinitSettings,
 };
-use bevy_image_export::{ImageExportSource, NDIExport, NDIExportBundle, NDIExportPlugin};
-use std::f32::consts::PI;
+use bevy_image_export::{
+    ImageExportSource, NDIColorFormat, NDIConfig, NDIExport, NDIExportBundle, NDIExportPlugin,
+    NDIFrameFormat, NDIRuntime,
+};
+use std::{f32::consts::PI, time::Duration};
 
 fn main() {
     App::new()
@@ -29,9 +32,7 @@ fn main() {
             }),
             NDIExportPlugin,
             bevy::diagnostic::FrameTimeDiagnosticsPlugin,
-            bevy::diagnostic::LogDiagnosticsPlugin {
-                ..default()
-            },
+            bevy::diagnostic::LogDiagnosticsPlugin { ..default() },
         ))
         .insert_resource(AmbientLight {
             color: Color::WHITE,
@@ -48,6 +49,7 @@ fn setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut images: ResMut<Assets<Image>>,
     mut export_sources: ResMut<Assets<ImageExportSource>>,
+    ndi_runtime: Res<NDIRuntime>,
 ) {
     let output_texture_handle = {
         let size = Extent3d {
@@ -90,7 +92,20 @@ fn setup(
             });
         });
 
-    match NDIExport::new("chatbox".to_string()) {
+    let config = NDIConfig {
+        name: "chatbox".into(),
+        groups: None,
+        clock_video: false,
+        clock_audio: false,
+        color_format: NDIColorFormat::Rgba,
+        frame_format: NDIFrameFormat::Progressive,
+        frame_rate: (60, 1),
+        stride_alignment: None,
+        max_queued_frames: 2,
+        dropped_frames_metadata_interval: Some(Duration::from_secs(1)),
+    };
+    match NDIExport::with_config(config) {
+        Err(_) if !ndi_runtime.is_available() => eprintln!("NDI runtime not found, not exporting"),
         Err(e) => eprintln!("failed to initialize NDIExport: {e}"),
         Ok(ndi_export) => {
             commands.spawn(NDIExportBundle {
@@ -102,7 +117,7 @@ fn setup(
 
     commands.spawn((
         PbrBundle {
-            mesh: meshes.add(Mesh::try_from(shape::Cube::default()).unwrap()),
+            mesh: meshes.add(Mesh::from(shape::Cube::default())),
             material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
             ..default()
         },
@@ -0,0 +1,242 @@
+//! Measures how fast frames can be exported on this machine.
+//!
+//! Renders a trivial scene without vsync and writes every frame to disk, then reports the
+//! throughput along with the average readback and encode times taken from the timing log.
+//!
+//! ```sh
+//! cargo run --release --example bench_export -- [WIDTHxHEIGHT] [FRAMES] [EXTENSION]
+//! ```
+//!
+//! Defaults to 1920x1080, 300 frames and `png`. Frames are written to `./bench_export`.
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    render::{camera::RenderTarget, render_resource::TextureFormat},
+    window::PresentMode,
+    winit::WinitSettings,
+};
+use bevy_image_export::{
+    FrameExported, ImageExportBundle, ImageExportPlugin, ImageExportSettings, ImageExportSource,
+};
+use std::{
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+
+const OUTPUT_DIR: &str = "bench_export";
+const TIMING_LOG: &str = "bench_export/timing.csv";
+/// Frames exported before measuring, while pipelines compile and buffers are allocated.
+const WARMUP_FRAMES: u32 = 30;
+
+#[derive(Resource, Clone, Debug)]
+struct BenchConfig {
+    size: UVec2,
+    frames: u32,
+    extension: String,
+}
+
+impl BenchConfig {
+    fn from_args() -> Self {
+        let mut args = std::env::args().skip(1);
+        let size = args
+            .next()
+            .and_then(|size| {
+                let (width, height) = size.split_once('x')?;
+                Some(UVec2::new(width.parse().ok()?, height.parse().ok()?))
+            })
+            .unwrap_or(UVec2::new(1920, 1080));
+        let frames = args
+            .next()
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(300);
+        let extension = args.next().unwrap_or_else(|| "png".into());
+        Self {
+            size,
+            frames,
+            extension,
+        }
+    }
+}
+
+/// Frames exported since the warmup and when the first of them was written.
+#[derive(Resource, Default)]
+struct BenchProgress {
+    exported: u32,
+    start: Option<Instant>,
+}
+
+fn main() {
+    let config = BenchConfig::from_args();
+    std::fs::create_dir_all(OUTPUT_DIR).expect("failed to create the output directory");
+
+    App::new()
+        .insert_resource(WinitSettings {
+            return_from_run: true,
+            ..default()
+        })
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    present_mode: PresentMode::AutoNoVsync,
+                    ..default()
+                }),
+                ..default()
+            }),
+            ImageExportPlugin::disk(".").with_timing_log(TIMING_LOG),
+        ))
+        .insert_resource(config)
+        .init_resource::<BenchProgress>()
+        .add_systems(Startup, setup)
+        .add_systems(Update, (update, measure))
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    config: Res<BenchConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut export_sources: ResMut<Assets<ImageExportSource>>,
+) {
+    info!(
+        "exporting {} {}x{} {} frames",
+        config.frames, config.size.x, config.size.y, config.extension
+    );
+    let output_texture_handle = images
+        .add(ImageExportSource::target_image(config.size, TextureFormat::Rgba8UnormSrgb).unwrap());
+
+    commands.spawn(Camera3dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(output_texture_handle.clone()),
+            ..default()
+        },
+        transform: Transform::from_translation(4.2 * Vec3::Z),
+        ..default()
+    });
+    commands.spawn(Camera2dBundle::default());
+
+    commands.spawn(ImageExportBundle {
+        source: export_sources.add(output_texture_handle.into()),
+        settings: ImageExportSettings {
+            output_dir: OUTPUT_DIR.into(),
+            extension: config.extension.clone(),
+            ..default()
+        },
+        ..default()
+    });
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(shape::Cube::default().into()),
+            material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+            ..default()
+        },
+        Moving,
+    ));
+    commands.spawn(PointLightBundle {
+        transform: Transform::from_xyz(2.0, 4.0, 4.0),
+        ..default()
+    });
+}
+
+#[derive(Component)]
+struct Moving;
+fn update(mut transforms: Query<&mut Transform, With<Moving>>, mut frame: Local<u32>) {
+    let theta = *frame as f32 * 0.01 * PI;
+    *frame += 1;
+    for mut transform in &mut transforms {
+        transform.rotation = Quat::from_rotation_y(theta) * Quat::from_rotation_x(0.5 * theta);
+    }
+}
+
+fn measure(
+    config: Res<BenchConfig>,
+    mut progress: ResMut<BenchProgress>,
+    mut exported: EventReader<FrameExported>,
+    mut exit: EventWriter<AppExit>,
+    mut warmup: Local<u32>,
+) {
+    for _ in exported.read() {
+        if *warmup < WARMUP_FRAMES {
+            *warmup += 1;
+            if *warmup == WARMUP_FRAMES {
+                progress.start = Some(Instant::now());
+            }
+            continue;
+        }
+        progress.exported += 1;
+    }
+    let Some(start) = progress.start else {
+        return;
+    };
+    if progress.exported < config.frames {
+        return;
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let fps = progress.exported as f64 / elapsed;
+    println!(
+        "{}x{} {}: {} frames in {elapsed:.2}s, {fps:.1} frames/s, {:.2} ms/frame",
+        config.size.x,
+        config.size.y,
+        config.extension,
+        progress.exported,
+        1000.0 / fps
+    );
+    match read_timings() {
+        Ok(timings) => println!("{timings}"),
+        Err(e) => error!("failed to read {TIMING_LOG}: {e}"),
+    }
+    exit.send(AppExit);
+}
+
+/// Averages of the timing log rows after the warmup.
+struct Timings {
+    readback: Duration,
+    encode: Duration,
+    captured: u32,
+    dropped: u32,
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "readback {:.2} ms/frame, encode {:.2} ms/frame, {} of {} frames dropped",
+            self.readback.as_secs_f64() * 1000.0,
+            self.encode.as_secs_f64() * 1000.0,
+            self.dropped,
+            self.captured + self.dropped
+        )
+    }
+}
+
+fn read_timings() -> Result<Timings, Box<dyn std::error::Error>> {
+    let log = std::fs::read_to_string(TIMING_LOG)?;
+    let (mut readback, mut encode) = (Duration::ZERO, Duration::ZERO);
+    let (mut captured, mut dropped) = (0, 0);
+    // Columns: render_frame,timestamp_ms,entity,export_frame,readback_us,encode_us,dropped
+    for row in log.lines().skip(1 + WARMUP_FRAMES as usize) {
+        let columns: Vec<&str> = row.split(',').collect();
+        if columns.get(6) == Some(&"true") {
+            dropped += 1;
+            continue;
+        }
+        let (Some(readback_us), Some(encode_us)) = (columns.get(4), columns.get(5)) else {
+            continue;
+        };
+        readback += Duration::from_micros(readback_us.parse()?);
+        encode += Duration::from_micros(encode_us.parse()?);
+        captured += 1;
+    }
+    if captured == 0 {
+        return Err("no frames were captured".into());
+    }
+    Ok(Timings {
+        readback: readback / captured,
+        encode: encode / captured,
+        captured,
+        dropped,
+    })
+}
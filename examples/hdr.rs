@@ -14,8 +14,7 @@ use bevy_image_export::{ImageExportBundle, ImageExportPlugin, ImageExportSource}
 use std::f32::consts::PI;
 
 fn main() {
-    let export_plugin = ImageExportPlugin::default();
-    let export_threads = export_plugin.threads.clone();
+    let export_plugin = ImageExportPlugin::disk(".");
 
     App::new()
         .insert_resource(WinitSettings {
@@ -35,8 +34,6 @@ fn main() {
         .add_systems(Startup, setup)
         .add_systems(Update, update)
         .run();
-
-    export_threads.finish();
 }
 
 fn setup(
@@ -103,6 +100,7 @@ fn setup(
 
     commands.spawn(ImageExportBundle {
         source: exporter_sources.add(output_texture_handle.into()),
+        ..default()
     });
 
     commands.insert_resource(AmbientLight {
@@ -0,0 +1,81 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    },
+    winit::WinitSettings,
+};
+use bevy_image_export::{
+    ImageExportBundle, ImageExportPlugin, ImageExportSettings, ImageExportSource,
+};
+
+const SIZE: u32 = 256;
+
+// Exports a texture that is filled procedurally every frame, without any camera rendering
+// into it. A texture written by a compute shader works the same way.
+fn main() {
+    App::new()
+        .insert_resource(WinitSettings {
+            return_from_run: true,
+            ..default()
+        })
+        .add_plugins((DefaultPlugins, ImageExportPlugin::disk(".")))
+        .add_systems(Startup, setup)
+        .add_systems(Update, fill)
+        .run();
+}
+
+#[derive(Resource)]
+struct Procedural(Handle<Image>);
+
+fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut export_sources: ResMut<Assets<ImageExportSource>>,
+) {
+    let size = Extent3d {
+        width: SIZE,
+        height: SIZE,
+        ..default()
+    };
+    let mut texture = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
+                | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    texture.resize(size);
+    let texture_handle = images.add(texture);
+
+    commands.spawn(ImageExportBundle {
+        source: export_sources.add(texture_handle.clone().into()),
+        settings: ImageExportSettings {
+            output_dir: "out/procedural".into(),
+            ..default()
+        },
+        ..default()
+    });
+    commands.insert_resource(Procedural(texture_handle));
+}
+
+fn fill(procedural: Res<Procedural>, mut images: ResMut<Assets<Image>>, mut frame: Local<u32>) {
+    let t = *frame as f32 * 0.05;
+    *frame += 1;
+    let Some(image) = images.get_mut(&procedural.0) else {
+        return;
+    };
+    for (i, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+        let (x, y) = ((i as u32 % SIZE) as f32, (i as u32 / SIZE) as f32);
+        let v = ((x * 0.05 + t).sin() + (y * 0.07 - t).cos()) * 0.25 + 0.5;
+        pixel.copy_from_slice(&[(v * 255.0) as u8, 64, ((1.0 - v) * 255.0) as u8, 255]);
+    }
+}
@@ -0,0 +1,144 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    window::WindowResolution,
+    winit::WinitSettings,
+};
+use bevy_image_export::{
+    ExportRenderLayers, ImageExportBundle, ImageExportPlugin, ImageExportSource,
+};
+use std::f32::consts::PI;
+
+// The exported scene lives on layer 1, debug gizmos live on layer 2.
+const SCENE_LAYER: u8 = 1;
+const GIZMO_LAYER: u8 = 2;
+
+fn main() {
+    App::new()
+        .insert_resource(WinitSettings {
+            return_from_run: true,
+            ..default()
+        })
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: WindowResolution::new(768.0, 768.0).with_scale_factor_override(1.0),
+                    ..default()
+                }),
+                ..default()
+            }),
+            ImageExportPlugin::disk("."),
+        ))
+        .insert_resource(AmbientLight {
+            color: Color::WHITE,
+            brightness: 1.0,
+        })
+        .insert_resource(GizmoConfig {
+            render_layers: RenderLayers::layer(GIZMO_LAYER),
+            ..default()
+        })
+        .add_systems(Startup, setup)
+        .add_systems(Update, (update, draw_gizmos))
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut export_sources: ResMut<Assets<ImageExportSource>>,
+) {
+    let output_texture_handle = {
+        let size = Extent3d {
+            width: 768,
+            height: 768,
+            ..default()
+        };
+        let mut export_texture = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        export_texture.resize(size);
+
+        images.add(export_texture)
+    };
+
+    let export_camera = Camera {
+        target: RenderTarget::Image(output_texture_handle),
+        ..default()
+    };
+    let export_source = ImageExportSource::from_camera(&export_camera).unwrap();
+
+    // The window camera sees the scene and the gizmos.
+    commands
+        .spawn((
+            Camera3dBundle {
+                transform: Transform::from_translation(4.2 * Vec3::Z),
+                ..default()
+            },
+            RenderLayers::from_layers(&[SCENE_LAYER, GIZMO_LAYER]),
+        ))
+        .with_children(|parent| {
+            // The export camera renders into its own target. Cameras that share a target draw
+            // into the same texture, which is why the export camera must not reuse the window
+            // (or any other camera's) target.
+            parent.spawn(Camera3dBundle {
+                camera: export_camera,
+                ..default()
+            });
+        });
+
+    // Limits the export camera to the scene layer, so the gizmos never end up in the exported
+    // frames.
+    commands.spawn((
+        ImageExportBundle {
+            source: export_sources.add(export_source),
+            ..default()
+        },
+        ExportRenderLayers(RenderLayers::layer(SCENE_LAYER)),
+    ));
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(shape::Cube::default().into()),
+            material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+            ..default()
+        },
+        RenderLayers::layer(SCENE_LAYER),
+        Moving,
+    ));
+}
+
+fn draw_gizmos(mut gizmos: Gizmos, transforms: Query<&Transform, With<Moving>>) {
+    for transform in &transforms {
+        gizmos.cuboid(transform.with_scale(Vec3::splat(1.2)), Color::GREEN);
+    }
+}
+
+#[derive(Component)]
+struct Moving;
+fn update(mut transforms: Query<&mut Transform, With<Moving>>, mut frame: Local<u32>) {
+    let theta = *frame as f32 * 0.25 * PI;
+    *frame += 1;
+    for mut transform in &mut transforms {
+        transform.translation = Vec3::new(theta.sin(), theta.cos(), 0.0);
+    }
+}
@@ -0,0 +1,155 @@
+use bevy::{
+    core_pipeline::core_3d::{self, graph::node::END_MAIN_PASS_POST_PROCESSING},
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_graph::RenderGraph,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        RenderApp,
+    },
+    ui::draw_ui_graph::node::UI_PASS,
+    window::WindowResolution,
+    winit::WinitSettings,
+};
+use bevy_image_export::{
+    add_image_export_node_named, ImageExportBundle, ImageExportPlugin, ImageExportSettings,
+    ImageExportSource,
+};
+use std::f32::consts::PI;
+
+// Copies the camera's frame before the UI is drawn on top of it.
+const SCENE_NODE: &str = "image_export_scene";
+
+fn main() {
+    let mut app = App::new();
+    app.insert_resource(WinitSettings {
+        return_from_run: true,
+        ..default()
+    })
+    .add_plugins((
+        DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(Window {
+                resolution: WindowResolution::new(768.0, 768.0).with_scale_factor_override(1.0),
+                ..default()
+            }),
+            ..default()
+        }),
+        ImageExportPlugin::disk("."),
+    ))
+    .insert_resource(AmbientLight {
+        color: Color::WHITE,
+        brightness: 1.0,
+    })
+    .add_systems(Startup, setup)
+    .add_systems(Update, update);
+
+    let mut graph = app
+        .sub_app_mut(RenderApp)
+        .world
+        .resource_mut::<RenderGraph>();
+    let core_3d = graph.get_sub_graph_mut(core_3d::graph::NAME).unwrap();
+    add_image_export_node_named(
+        core_3d,
+        SCENE_NODE,
+        &[END_MAIN_PASS_POST_PROCESSING],
+        &[UI_PASS],
+    );
+
+    app.run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut export_sources: ResMut<Assets<ImageExportSource>>,
+) {
+    let output_texture_handle = {
+        let size = Extent3d {
+            width: 768,
+            height: 768,
+            ..default()
+        };
+        let mut export_texture = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        export_texture.resize(size);
+
+        images.add(export_texture)
+    };
+
+    commands.spawn(Camera3dBundle {
+        camera: Camera {
+            target: RenderTarget::Image(output_texture_handle.clone()),
+            ..default()
+        },
+        transform: Transform::from_translation(4.2 * Vec3::Z),
+        ..default()
+    });
+
+    // Both exports read the same image. The scene source is copied by the node running
+    // before the UI pass, the composite source by the default node after the camera driver.
+    let mut scene_source = ImageExportSource::new(output_texture_handle.clone());
+    scene_source.set_node(SCENE_NODE);
+    commands.spawn(ImageExportBundle {
+        source: export_sources.add(scene_source),
+        settings: ImageExportSettings {
+            output_dir: "out/scene".into(),
+            ..default()
+        },
+        ..default()
+    });
+    commands.spawn(ImageExportBundle {
+        source: export_sources.add(ImageExportSource::new(output_texture_handle)),
+        settings: ImageExportSettings {
+            output_dir: "out/composite".into(),
+            ..default()
+        },
+        ..default()
+    });
+
+    commands.spawn(NodeBundle {
+        style: Style {
+            width: Val::Px(200.0),
+            height: Val::Px(40.0),
+            margin: UiRect::all(Val::Px(24.0)),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..default()
+    });
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(shape::Cube::default().into()),
+            material: materials.add(Color::rgb(1.0, 0.0, 0.0).into()),
+            ..default()
+        },
+        Moving,
+    ));
+}
+
+#[derive(Component)]
+struct Moving;
+fn update(mut transforms: Query<&mut Transform, With<Moving>>, mut frame: Local<u32>) {
+    let theta = *frame as f32 * 0.25 * PI;
+    *frame += 1;
+    for mut transform in &mut transforms {
+        transform.translation = Vec3::new(theta.sin(), theta.cos(), 0.0);
+    }
+}
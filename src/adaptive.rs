@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::disk::ImageExportSettings;
+use crate::events::ExportStats;
+
+/// Lowers the resolution of a disk export while it writes fewer frames per second than
+/// targeted, e.g. to keep a live stream smooth on variable hardware, and restores it once the
+/// export keeps up again.
+///
+/// The export's `scale` is changed in steps, at most once per `interval` so that
+/// [`ExportStats::achieved_fps`] can settle in between. It never exceeds the scale the export
+/// started with. Exports with a fixed `resize` aren't adapted.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveQuality {
+    pub target_fps: f32,
+    /// Lowest scale, relative to the scale the export started with.
+    pub min_scale: f32,
+    /// Change of the scale per step.
+    pub step: f32,
+    pub interval: Duration,
+}
+
+impl AdaptiveQuality {
+    pub fn new(target_fps: f32) -> Self {
+        Self {
+            target_fps,
+            min_scale: 0.25,
+            step: 0.125,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The scale an adaptive export started with and when its scale last changed.
+pub(crate) struct AdaptiveState {
+    full_scale: f32,
+    last_change: Duration,
+}
+
+pub(crate) fn adapt_export_quality(
+    mut exports: Query<(Entity, &mut ImageExportSettings, &ExportStats)>,
+    time: Res<Time>,
+    mut states: Local<HashMap<Entity, AdaptiveState>>,
+) {
+    let now = time.elapsed();
+    states.retain(|entity, _| exports.contains(*entity));
+    for (entity, mut settings, stats) in &mut exports {
+        let Some(adaptive) = settings.adaptive_quality else {
+            states.remove(&entity);
+            continue;
+        };
+        if settings.resize.is_some() || stats.frames_written == 0 {
+            continue;
+        }
+        let state = states.entry(entity).or_insert_with(|| AdaptiveState {
+            full_scale: settings.scale,
+            last_change: now,
+        });
+        if now < state.last_change + adaptive.interval {
+            continue;
+        }
+        // Tolerates some jitter around the target, so that the scale doesn't flip every step.
+        let scale = if stats.achieved_fps < adaptive.target_fps * 0.9 {
+            (settings.scale - adaptive.step).max(adaptive.min_scale * state.full_scale)
+        } else if stats.achieved_fps > adaptive.target_fps * 0.98 {
+            (settings.scale + adaptive.step).min(state.full_scale)
+        } else {
+            continue;
+        };
+        if scale != settings.scale {
+            info!(
+                "image export to {} writes {:.1} fps, changing its scale to {scale}",
+                settings.output_dir, stats.achieved_fps
+            );
+            settings.scale = scale;
+            state.last_change = now;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use ab_glyph::{point, Font, FontArc, ScaleFont};
+use image::{DynamicImage, GenericImage, GenericImageView, Pixel, Rgba};
+
+/// Corner of the frame the burn-in text is drawn into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BurnInPosition {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Text drawn into every frame written to disk, e.g. a frame number or timecode, see
+/// [`ImageExportSettings::burn_in`](crate::ImageExportSettings::burn_in). Streaming sinks such as
+/// a [`FifoExport`](crate::FifoExport) get the frames without it.
+#[derive(Clone, Debug)]
+pub struct BurnInSettings {
+    pub font: FontArc,
+    pub position: BurnInPosition,
+    /// Font size in pixels.
+    pub font_size: f32,
+    /// `{frame}` is replaced with the frame number, `{timecode}` with an `HH:MM:SS:FF`
+    /// timecode based on `frame_rate`.
+    pub template: String,
+    pub frame_rate: u32,
+    pub color: Rgba<u8>,
+}
+
+impl BurnInSettings {
+    pub fn new(font: FontArc) -> Self {
+        Self {
+            font,
+            position: BurnInPosition::default(),
+            font_size: 32.0,
+            template: "{frame}".into(),
+            frame_rate: 60,
+            color: Rgba([255, 255, 255, 255]),
+        }
+    }
+
+    fn text(&self, frame: u32) -> String {
+        let fps = self.frame_rate.max(1);
+        let seconds = frame / fps;
+        let timecode = format!(
+            "{:02}:{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            frame % fps
+        );
+        self.template
+            .replace("{frame}", &format!("{frame:05}"))
+            .replace("{timecode}", &timecode)
+    }
+
+    pub(crate) fn apply(&self, image: &mut DynamicImage, frame: u32) {
+        let text = self.text(frame);
+        let font = self.font.as_scaled(self.font_size);
+        let margin = self.font_size / 2.0;
+
+        let text_width: f32 = text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum();
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let x = match self.position {
+            BurnInPosition::TopLeft | BurnInPosition::BottomLeft => margin,
+            BurnInPosition::TopRight | BurnInPosition::BottomRight => width - margin - text_width,
+        };
+        let y = match self.position {
+            BurnInPosition::TopLeft | BurnInPosition::TopRight => margin,
+            BurnInPosition::BottomLeft | BurnInPosition::BottomRight => {
+                height - margin - font.height()
+            }
+        };
+
+        let mut caret = point(x, y + font.ascent());
+        for c in text.chars() {
+            let mut glyph = font.scaled_glyph(c);
+            glyph.position = caret;
+            caret.x += font.h_advance(glyph.id);
+            let Some(outlined) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let px = bounds.min.x as i64 + gx as i64;
+                let py = bounds.min.y as i64 + gy as i64;
+                if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                    return;
+                }
+                let (px, py) = (px as u32, py as u32);
+                let mut color = self.color;
+                color[3] = (color[3] as f32 * coverage.clamp(0.0, 1.0)) as u8;
+                let mut pixel = image.get_pixel(px, py);
+                pixel.blend(&color);
+                image.put_pixel(px, py, pixel);
+            });
+        }
+    }
+}
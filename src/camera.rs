@@ -0,0 +1,78 @@
+use bevy::{
+    prelude::*,
+    render::{camera::RenderTarget, render_resource::TextureUsages},
+    utils::HashSet,
+};
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::{ImageExportBundle, ImageExportSource};
+
+/// Exports what a camera renders into its target image, without creating the source by hand.
+///
+/// The [`ImageExportBundle`] is added to the camera entity once its target image is loaded.
+/// Target images without the `COPY_SRC` usage get it added. Cameras that don't render into an
+/// image are skipped with a warning and lose this component.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ExportThisCamera {
+    pub settings: ImageExportSettings,
+}
+
+pub(crate) fn export_marked_cameras(
+    mut commands: Commands,
+    cameras: Query<(Entity, &Camera, &ExportThisCamera), Without<Handle<ImageExportSource>>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sources: ResMut<Assets<ImageExportSource>>,
+) {
+    for (entity, camera, export) in &cameras {
+        let RenderTarget::Image(target) = &camera.target else {
+            warn!("camera {entity:?} is marked for export but doesn't render into an image");
+            commands.entity(entity).remove::<ExportThisCamera>();
+            continue;
+        };
+        let Some(image) = images.get(target) else {
+            continue;
+        };
+        if !image
+            .texture_descriptor
+            .usage
+            .contains(TextureUsages::COPY_SRC)
+        {
+            debug!("adding COPY_SRC to the target image of exported camera {entity:?}");
+            if let Some(image) = images.get_mut(target) {
+                image.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+            }
+        }
+
+        commands.entity(entity).insert(ImageExportBundle {
+            source: sources.add(ImageExportSource::new(target.clone())),
+            settings: export.settings.clone(),
+            ..default()
+        });
+    }
+}
+
+/// Warns once per source when several active cameras render into an exported image without
+/// [`ImageExportSource::set_capture_after_camera`], in which case the export captures the image
+/// after all of them.
+pub(crate) fn warn_shared_targets(
+    sources: Res<Assets<ImageExportSource>>,
+    cameras: Query<&Camera>,
+    mut warned: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (id, source) in sources.iter() {
+        if source.capture_after_camera().is_some() || warned.contains(&id) {
+            continue;
+        }
+        let writers = cameras
+            .iter()
+            .filter(|camera| {
+                camera.is_active
+                    && matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+            })
+            .count();
+        if writers > 1 {
+            warn!("{writers} cameras render into the image of export source {id:?}, capturing after the last one; use `ImageExportSource::set_capture_after_camera` to pick one");
+            warned.insert(id);
+        }
+    }
+}
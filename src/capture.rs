@@ -0,0 +1,265 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::{app::PluginsState, prelude::*, render::RenderApp, utils::HashSet};
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::{ExportedFrames, ImageExport, ImageExportSource};
+
+/// Determines which frames an export captures.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Captures every frame.
+    #[default]
+    Continuous,
+    /// Captures one frame per [`CaptureRequest`].
+    OnRequest,
+}
+
+/// Requests a single capture from an export using [`CaptureMode::OnRequest`].
+///
+/// Multiple requests sent before the export catches up are captured in consecutive frames.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CaptureRequest {
+    pub entity: Entity,
+}
+
+pub(crate) fn handle_capture_requests(
+    mut requests: EventReader<CaptureRequest>,
+    mut exports: Query<&mut ImageExport>,
+) {
+    for request in requests.read() {
+        match exports.get_mut(request.entity) {
+            Ok(mut export) => export.requested_captures = export.requested_captures.wrapping_add(1),
+            Err(_) => warn!(
+                "ignoring capture request for {:?}, which is not an image export",
+                request.entity
+            ),
+        }
+    }
+}
+
+/// Starts a new take: the export's frame numbering restarts at 0, optionally in another output
+/// directory.
+///
+/// Frames captured before the reset are written with the old numbering and directory.
+#[derive(Event, Clone, Debug)]
+pub struct ResetCapture {
+    pub entity: Entity,
+    /// Replaces [`ImageExportSettings::output_dir`].
+    pub output_dir: Option<String>,
+}
+
+pub(crate) fn handle_capture_resets(
+    mut resets: EventReader<ResetCapture>,
+    mut exports: Query<(&mut ImageExport, &mut ImageExportSettings)>,
+) {
+    for reset in resets.read() {
+        match exports.get_mut(reset.entity) {
+            Ok((mut export, mut settings)) => {
+                export.resets = export.resets.wrapping_add(1);
+                if let Some(output_dir) = &reset.output_dir {
+                    settings.output_dir = output_dir.clone();
+                }
+            }
+            Err(_) => warn!(
+                "ignoring capture reset for {:?}, which is not an image export",
+                reset.entity
+            ),
+        }
+    }
+}
+
+/// Returns a system sending a [`CaptureRequest`] to every export when `key` is pressed.
+pub fn capture_on_key(key: KeyCode) -> impl System<In = (), Out = ()> {
+    IntoSystem::into_system(
+        move |input: Res<Input<KeyCode>>,
+              exports: Query<Entity, With<ImageExport>>,
+              mut requests: EventWriter<CaptureRequest>| {
+            if input.just_pressed(key) {
+                requests.send_batch(exports.iter().map(|entity| CaptureRequest { entity }));
+            }
+        },
+    )
+}
+
+/// Shared between the worlds, set once the first frame was read back from any source.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct FirstCaptureSignal(pub Arc<AtomicBool>);
+
+/// Why [`wait_for_first_capture`] failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FirstCaptureError {
+    /// Neither [`ImageExportPlugin`](crate::ImageExportPlugin) nor
+    /// [`NDIExportPlugin`](crate::NDIExportPlugin) was added to the app.
+    PluginMissing,
+    /// No frame was read back within the timeout.
+    Timeout { timeout: Duration, updates: u32 },
+}
+
+impl fmt::Display for FirstCaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PluginMissing => write!(f, "no image export plugin was added to the app"),
+            Self::Timeout { timeout, updates } => write!(
+                f,
+                "no frame was captured within {timeout:?} ({updates} updates), check that the \
+                 exported image is the target of an active camera and has an export attached"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FirstCaptureError {}
+
+/// Updates `app` until the first frame was read back from any export source, e.g. to check
+/// that a headless app or test is set up correctly before running it. Finishes adding the
+/// plugins first if that's still pending.
+///
+/// Returns an error if nothing was captured within `timeout`, instead of silently producing
+/// no output.
+pub fn wait_for_first_capture(app: &mut App, timeout: Duration) -> Result<(), FirstCaptureError> {
+    finish_plugins(app);
+    let signal = app
+        .world
+        .get_resource::<FirstCaptureSignal>()
+        .ok_or(FirstCaptureError::PluginMissing)?
+        .0
+        .clone();
+
+    let start = Instant::now();
+    let mut updates = 0;
+    while !signal.load(Ordering::Acquire) {
+        if start.elapsed() > timeout {
+            return Err(FirstCaptureError::Timeout { timeout, updates });
+        }
+        app.update();
+        updates += 1;
+    }
+    Ok(())
+}
+
+/// Sources read back by [`capture_frame`] regardless of their exports.
+#[derive(Resource, Default)]
+pub(crate) struct SyncCaptures(pub HashSet<AssetId<ImageExportSource>>);
+
+/// Runs a single `app.update()` and returns the frame of `source` it rendered, e.g. to assert
+/// pixel values in tests without waiting for events. Finishes adding the plugins first if
+/// that's still pending.
+///
+/// The source doesn't need an export. Returns `None` if the frame couldn't be read back, e.g.
+/// because the source isn't prepared on the GPU yet or exports are paused, or if the render app
+/// runs on its own thread with pipelined rendering.
+pub fn capture_frame(app: &mut App, source: &Handle<ImageExportSource>) -> Option<Image> {
+    finish_plugins(app);
+    let id = source.id();
+    app.get_sub_app_mut(RenderApp)
+        .ok()?
+        .world
+        .get_resource_mut::<SyncCaptures>()?
+        .0
+        .insert(id);
+    app.update();
+
+    let render_world = &mut app.get_sub_app_mut(RenderApp).ok()?.world;
+    render_world.resource_mut::<SyncCaptures>().0.remove(&id);
+    render_world
+        .resource::<ExportedFrames>()
+        .get_unpadded(id)
+        .map(Cow::into_owned)
+}
+
+fn finish_plugins(app: &mut App) {
+    if app.plugins_state() == PluginsState::Ready {
+        app.finish();
+        app.cleanup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        core_pipeline::clear_color::ClearColorConfig,
+        log::LogPlugin,
+        render::{
+            camera::RenderTarget,
+            render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        },
+        window::ExitCondition,
+        winit::WinitPlugin,
+    };
+
+    use super::*;
+    use crate::ImageExportPlugin;
+
+    #[test]
+    fn capture_frame_needs_a_render_app() {
+        let mut app = App::new();
+        assert!(capture_frame(&mut app, &Handle::default()).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn capture_frame_reads_back_the_rendered_frame() {
+        let mut app = App::new();
+        app.add_plugins((
+            DefaultPlugins
+                .build()
+                .disable::<LogPlugin>()
+                .disable::<WinitPlugin>()
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                }),
+            ImageExportPlugin::default(),
+        ));
+        let size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.texture_descriptor.usage |=
+            TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+        let image = app.world.resource_mut::<Assets<Image>>().add(image);
+        let source = app
+            .world
+            .resource_mut::<Assets<ImageExportSource>>()
+            .add(ImageExportSource::new(image.clone()));
+        app.world.spawn(Camera2dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(image),
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(Color::RED),
+            },
+            ..default()
+        });
+
+        // The source is prepared on the GPU during the first updates.
+        let frame = (0..10)
+            .find_map(|_| {
+                capture_frame(&mut app, &source).filter(|frame| frame.data[..4] != [0, 0, 0, 255])
+            })
+            .expect("no frame was rendered");
+        assert_eq!(frame.texture_descriptor.size, size);
+        assert!(frame
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+}
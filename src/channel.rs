@@ -0,0 +1,103 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bevy::{
+    core::FrameCount,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_asset::RenderAssets},
+    utils::HashSet,
+};
+use crossbeam_channel::{Sender, TrySendError};
+
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+/// A frame sent by a [`ChannelExport`].
+#[derive(Clone, Debug)]
+pub struct ChannelFrame {
+    /// The exporting entity.
+    pub entity: Entity,
+    /// The render frame, as counted by [`FrameCount`].
+    pub render_frame: u32,
+    /// The frame without row padding, in the source's texture format. Shared with the other
+    /// sinks of the frame, use `Arc::unwrap_or_clone` to own it.
+    pub image: Arc<Image>,
+}
+
+/// Sends every exported frame into a `crossbeam_channel`, e.g. to consume frames on a thread
+/// outside the bevy schedule.
+///
+/// Frames are dropped while a bounded channel is full. Once the receiver is dropped, the export
+/// stops sending with a warning.
+#[derive(Component, Clone)]
+pub struct ChannelExport {
+    sender: Sender<ChannelFrame>,
+    disconnected: Arc<AtomicBool>,
+}
+
+impl ChannelExport {
+    pub fn new(sender: Sender<ChannelFrame>) -> Self {
+        Self {
+            sender,
+            disconnected: default(),
+        }
+    }
+
+    /// Whether the receiver was dropped, which stops the export.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected.load(Ordering::Relaxed)
+    }
+}
+
+impl ExtractComponent for ChannelExport {
+    type Query = (&'static ChannelExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (ChannelExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        (!this.is_disconnected()).then(|| (this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct ChannelExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: ChannelExport,
+}
+
+pub(crate) fn send_to_channels(
+    exports: Query<(Entity, &ChannelExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    frame_count: Res<FrameCount>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (entity, export, source_handle) in &exports {
+        let id = source_handle.id();
+        if export.is_disconnected()
+            || !source_is_present(id, &sources, &images, &mut missing_sources)
+        {
+            continue;
+        }
+        let Some(frame) = frames.get_export_shared(entity, id) else {
+            continue;
+        };
+        match export.sender.try_send(ChannelFrame {
+            entity,
+            render_frame: frame_count.0,
+            image: frame,
+        }) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!("dropping frame of image source {id:?}, the channel of {entity:?} is full");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("the receiver of channel export {entity:?} was dropped, stopping the export");
+                export.disconnected.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
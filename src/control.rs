@@ -0,0 +1,32 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        RenderApp,
+    },
+};
+
+use crate::capture::FirstCaptureSignal;
+
+/// Master switch for all exports.
+///
+/// While paused, no frames are read back or copied into an
+/// [`ImageExportGpuDestination`](crate::ImageExportGpuDestination), so every sink (disk, NDI,
+/// FIFO, ...) stops without touching its components. Capture requests are kept until the exports
+/// resume with the live frame.
+#[derive(Resource, ExtractResource, Clone, Copy, Debug, Default)]
+pub struct GlobalExportControl {
+    pub paused: bool,
+}
+
+/// Inserts [`GlobalExportControl`] and extracts it into the render world. Also shares the
+/// signal for the first capture between the worlds.
+pub(crate) fn add_export_control(app: &mut App) {
+    let signal = FirstCaptureSignal::default();
+    app.init_resource::<GlobalExportControl>()
+        .insert_resource(signal.clone())
+        .add_plugins(ExtractResourcePlugin::<GlobalExportControl>::default());
+    if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+        render_app.insert_resource(signal);
+    }
+}
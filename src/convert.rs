@@ -0,0 +1,709 @@
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+
+/// Converts an exported frame into another texture format.
+///
+/// Supported source formats are 8-bit RGBA/BGRA (linear or sRGB), `Rgba16Float` and
+/// `Rgba32Float`. Supported targets are 8-bit RGBA/BGRA (linear or sRGB), `Rgba32Float`, and
+/// `R8Unorm`/`R32Float`, which keep only the red channel. Values are clamped when converting
+/// float formats to 8-bit ones. Returns `None` if either format is unsupported.
+pub fn convert_export_image(src: &Image, target: TextureFormat) -> Option<Image> {
+    convert_export_image_dithered(src, target, Dithering::None)
+}
+
+/// How higher precision frames are reduced to 8 bits per channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dithering {
+    /// Rounds every value to the nearest 8-bit one, which can cause banding in smooth
+    /// gradients.
+    #[default]
+    None,
+    /// Adds an 8x8 Bayer pattern before rounding, trading banding for fine noise.
+    Ordered,
+}
+
+/// Like [`convert_export_image`], but applies `dithering` when converting float formats to
+/// 8-bit ones.
+pub fn convert_export_image_dithered(
+    src: &Image,
+    target: TextureFormat,
+    dithering: Dithering,
+) -> Option<Image> {
+    let format = src.texture_descriptor.format;
+    if format == target {
+        return Some(src.clone());
+    }
+
+    let data = if is_rgba8(format) && is_rgba8(target) && format.is_srgb() == target.is_srgb() {
+        let mut data = src.data.clone();
+        if is_bgra8(format) != is_bgra8(target) {
+            for pixel in data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        data
+    } else {
+        let width = (dithering == Dithering::Ordered).then(|| src.width() as usize);
+        encode(&decode(src)?, target, width)?
+    };
+
+    Some(with_data(src, target, data))
+}
+
+/// Extracts one channel (0 = red, 1 = green, 2 = blue, 3 = alpha) of an exported frame into
+/// an `R8Unorm` image, e.g. to save a mask stored in the alpha channel.
+pub fn extract_export_channel(src: &Image, channel: usize) -> Option<Image> {
+    if channel > 3 {
+        return None;
+    }
+    let data = if is_rgba8(src.texture_descriptor.format) {
+        let channel = match (channel, is_bgra8(src.texture_descriptor.format)) {
+            (0, true) => 2,
+            (2, true) => 0,
+            (channel, _) => channel,
+        };
+        src.data
+            .chunks_exact(4)
+            .map(|pixel| pixel[channel])
+            .collect()
+    } else {
+        decode(src)?
+            .iter()
+            .map(|pixel| unorm8(pixel[channel]))
+            .collect()
+    };
+
+    Some(with_data(src, TextureFormat::R8Unorm, data))
+}
+
+/// How the channels of a frame are interpreted before it is encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportChannelMode {
+    /// Exports the frame's colors as they are.
+    #[default]
+    Color,
+    /// Treats the frame as signed normals in `[-1, 1]` and remaps them to `[0, 1]`, see
+    /// [`encode_normals`].
+    NormalEncode,
+}
+
+/// Remaps signed normals in `[-1, 1]`, e.g. from a view-space normal buffer, to `[0, 1]` via
+/// `n * 0.5 + 0.5` and stores them in an opaque `Rgba8Unorm` image, the usual normal map
+/// encoding. Negative components would otherwise be clamped to black.
+pub fn encode_normals(src: &Image) -> Option<Image> {
+    let data = decode(src)?
+        .iter()
+        .flat_map(|pixel| {
+            let [x, y, z, _] = pixel.map(|c| c * 0.5 + 0.5);
+            [x, y, z, 1.0].map(unorm8)
+        })
+        .collect();
+
+    Some(with_data(src, TextureFormat::Rgba8Unorm, data))
+}
+
+/// Colormap single-channel frames, e.g. depth or stencil, are visualized with, see
+/// [`apply_colormap`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Google's Turbo, a rainbow map with smooth perceptual steps, mapping low values to
+    /// blue and high values to red.
+    #[default]
+    Turbo,
+    /// Matplotlib's perceptually uniform viridis, from dark purple to yellow.
+    Viridis,
+}
+
+impl Colormap {
+    /// The sRGB color of `t` in `[0, 1]`, using polynomial fits of the maps.
+    fn color(self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Colormap::Turbo => {
+                let powers = [1.0, t, t * t, t * t * t, t * t * t * t, t * t * t * t * t];
+                let dot = |coefficients: [f32; 6]| {
+                    powers.iter().zip(coefficients).map(|(p, c)| p * c).sum()
+                };
+                [
+                    dot([
+                        0.135_721_38,
+                        4.615_392_6,
+                        -42.660_323,
+                        132.131_08,
+                        -152.942_4,
+                        59.286_38,
+                    ]),
+                    dot([
+                        0.091_402_61,
+                        2.194_188_4,
+                        4.842_966_6,
+                        -14.185_033,
+                        4.277_298_6,
+                        2.829_566,
+                    ]),
+                    dot([
+                        0.106_673_3,
+                        12.641_946,
+                        -60.582_05,
+                        110.362_77,
+                        -89.903_11,
+                        27.348_25,
+                    ]),
+                ]
+            }
+            Colormap::Viridis => {
+                const COEFFICIENTS: [[f32; 3]; 7] = [
+                    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+                    [0.105_093_04, 1.404_613_5, 1.384_590_2],
+                    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+                    [-4.634_230_4, -5.799_101, -19.332_441],
+                    [6.228_27, 14.179_933, 56.690_55],
+                    [4.776_385, -13.745_145, -65.353_03],
+                    [-5.435_456, 4.645_852_6, 26.312_435],
+                ];
+                // Horner's scheme, starting with the highest power.
+                COEFFICIENTS
+                    .iter()
+                    .rev()
+                    .fold([0.0; 3], |acc, c| [0, 1, 2].map(|i| acc[i] * t + c[i]))
+            }
+        }
+    }
+
+    /// Lookup table of the map's 8-bit sRGB colors.
+    fn lut(self) -> [[u8; 3]; 256] {
+        std::array::from_fn(|i| self.color(i as f32 / 255.0).map(unorm8))
+    }
+}
+
+/// Maps a single-channel frame through `colormap` into an opaque `Rgba8UnormSrgb` image, making
+/// depth maps or stencil masks readable at a glance.
+///
+/// Values are normalized to the range between the smallest and largest finite value of the
+/// frame, non-finite values are mapped to black. Supports `R8Unorm`, `R16Unorm`, `R16Float`,
+/// `R32Float`, `Stencil8`, `Depth16Unorm` and `Depth32Float` sources; note that Bevy's reversed
+/// depth buffers store near geometry as high values.
+pub fn apply_colormap(src: &Image, colormap: Colormap) -> Option<Image> {
+    let values = decode_single_channel(src)?;
+    let (min, max) = values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    let range = max - min;
+    let lut = colormap.lut();
+    let data = values
+        .iter()
+        .flat_map(|&value| {
+            if !value.is_finite() {
+                return [0, 0, 0, 255];
+            }
+            let t = if range > 0.0 {
+                (value - min) / range
+            } else {
+                0.0
+            };
+            let [r, g, b] = lut[unorm8(t) as usize];
+            [r, g, b, 255]
+        })
+        .collect();
+
+    Some(with_data(src, TextureFormat::Rgba8UnormSrgb, data))
+}
+
+/// Converts a depth frame rendered with `projection` into view-space distances along the
+/// camera's forward axis, e.g. for depth datasets. Returns an `R32Float` image holding the
+/// distances in world units, which is infinite where Bevy's infinite reversed-Z projection
+/// stores a depth of 0.
+///
+/// Supports the single-channel formats of [`apply_colormap`] except `Stencil8`.
+pub fn linearize_depth(src: &Image, projection: Mat4) -> Option<Image> {
+    if src.texture_descriptor.format == TextureFormat::Stencil8 {
+        return None;
+    }
+    let values = decode_single_channel(src)?;
+    // Unprojects (0, 0, depth, 1); only the z and w rows of the inverse are needed.
+    let inverse = projection.inverse();
+    let (z, w) = (inverse.row(2), inverse.row(3));
+    let data = values
+        .iter()
+        .flat_map(|&depth| {
+            let view_z = z.z * depth + z.w;
+            let view_w = w.z * depth + w.w;
+            let distance = if view_w == 0.0 {
+                f32::INFINITY
+            } else {
+                -view_z / view_w
+            };
+            distance.to_le_bytes()
+        })
+        .collect();
+
+    Some(with_data(src, TextureFormat::R32Float, data))
+}
+
+/// Decodes the values of a single-channel image.
+fn decode_single_channel(src: &Image) -> Option<Vec<f32>> {
+    let data = &src.data;
+    let values = match src.texture_descriptor.format {
+        TextureFormat::R8Unorm => data.iter().map(|&v| v as f32 / 255.0).collect(),
+        TextureFormat::Stencil8 => data.iter().map(|&v| v as f32).collect(),
+        TextureFormat::R16Unorm | TextureFormat::Depth16Unorm => data
+            .chunks_exact(2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]) as f32 / 65535.0)
+            .collect(),
+        TextureFormat::R16Float => data
+            .chunks_exact(2)
+            .map(|v| f16_to_f32(u16::from_le_bytes([v[0], v[1]])))
+            .collect(),
+        TextureFormat::R32Float | TextureFormat::Depth32Float => data
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+        _ => return None,
+    };
+    Some(values)
+}
+
+/// Color matrix used to convert RGB to YUV.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// BT.601, for standard definition video.
+    Bt601,
+    /// BT.709, for HD video.
+    #[default]
+    Bt709,
+}
+
+impl YuvMatrix {
+    /// The red and blue luma coefficients.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// Plane layout of YUV 4:2:0 frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Yuv420Layout {
+    /// A luma plane followed by one plane of interleaved U and V samples.
+    #[default]
+    Nv12,
+    /// A luma plane followed by a U and a V plane.
+    I420,
+}
+
+/// YUV 4:2:0 format frames are converted to, see [`encode_yuv420`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct YuvFormat {
+    pub layout: Yuv420Layout,
+    pub matrix: YuvMatrix,
+}
+
+/// Converts an exported frame to limited range YUV 4:2:0, as expected by most (hardware) video
+/// encoders, e.g. `-pix_fmt nv12` in ffmpeg. This takes 1.5 bytes per pixel instead of 4.
+///
+/// Chroma is averaged over blocks of 2x2 pixels; frames with an odd size get a chroma plane of
+/// `ceil(width / 2)` by `ceil(height / 2)` samples. Supports the source formats of
+/// [`convert_export_image`].
+pub fn encode_yuv420(src: &Image, format: YuvFormat) -> Option<Vec<u8>> {
+    let (width, height) = (src.width() as usize, src.height() as usize);
+    let src_format = src.texture_descriptor.format;
+    // Video stores gamma encoded values, so linear colors are encoded with the sRGB curve.
+    let rgb: Vec<[f32; 3]> = if is_rgba8(src_format) {
+        src.data
+            .chunks_exact(4)
+            .map(|pixel| {
+                let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+                if is_bgra8(src_format) {
+                    [rgb[2], rgb[1], rgb[0]]
+                } else {
+                    rgb
+                }
+            })
+            .collect()
+    } else {
+        decode(src)?
+            .iter()
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]].map(|c| linear_to_srgb(c.clamp(0.0, 1.0))))
+            .collect()
+    };
+    if rgb.len() != width * height {
+        return None;
+    }
+
+    let (kr, kb) = format.matrix.coefficients();
+    let luma = |[r, g, b]: [f32; 3]| kr * r + (1.0 - kr - kb) * g + kb * b;
+    let mut data: Vec<u8> = rgb
+        .iter()
+        .map(|&pixel| (16.0 + 219.0 * luma(pixel)).round().clamp(0.0, 255.0) as u8)
+        .collect();
+
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut u = Vec::with_capacity(chroma_width * chroma_height);
+    let mut v = Vec::with_capacity(chroma_width * chroma_height);
+    for y in 0..chroma_height {
+        for x in 0..chroma_width {
+            let mut sum = [0.0; 3];
+            let mut count = 0.0;
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (px, py) = (2 * x + dx, 2 * y + dy);
+                if px < width && py < height {
+                    let pixel = rgb[py * width + px];
+                    for (sum, c) in sum.iter_mut().zip(pixel) {
+                        *sum += c;
+                    }
+                    count += 1.0;
+                }
+            }
+            let [r, g, b] = sum.map(|c| c / count);
+            let y = luma([r, g, b]);
+            let chroma = |c: f32| (128.0 + 224.0 * c).round().clamp(0.0, 255.0) as u8;
+            u.push(chroma((b - y) / (2.0 * (1.0 - kb))));
+            v.push(chroma((r - y) / (2.0 * (1.0 - kr))));
+        }
+    }
+    match format.layout {
+        Yuv420Layout::Nv12 => data.extend(u.iter().zip(&v).flat_map(|(&u, &v)| [u, v])),
+        Yuv420Layout::I420 => {
+            data.extend(u);
+            data.extend(v);
+        }
+    }
+    Some(data)
+}
+
+/// Copy of `src`'s descriptors with `format` and `data`.
+pub(crate) fn with_data(src: &Image, format: TextureFormat, data: Vec<u8>) -> Image {
+    let mut texture_descriptor = src.texture_descriptor.clone();
+    texture_descriptor.format = format;
+    Image {
+        data,
+        texture_descriptor,
+        sampler: src.sampler.clone(),
+        texture_view_descriptor: src.texture_view_descriptor.clone(),
+    }
+}
+
+fn is_rgba8(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    ) || is_bgra8(format)
+}
+
+fn is_bgra8(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// Decodes the pixels of an image into linear RGBA values.
+fn decode(src: &Image) -> Option<Vec<[f32; 4]>> {
+    let format = src.texture_descriptor.format;
+    let pixels = if is_rgba8(format) {
+        src.data
+            .chunks_exact(4)
+            .map(|pixel| {
+                let mut rgba = [0, 1, 2, 3].map(|i| pixel[i] as f32 / 255.0);
+                if is_bgra8(format) {
+                    rgba.swap(0, 2);
+                }
+                if format.is_srgb() {
+                    for c in &mut rgba[..3] {
+                        *c = srgb_to_linear(*c);
+                    }
+                }
+                rgba
+            })
+            .collect()
+    } else if format == TextureFormat::Rgba16Float {
+        src.data
+            .chunks_exact(8)
+            .map(|pixel| {
+                [0, 1, 2, 3]
+                    .map(|i| f16_to_f32(u16::from_le_bytes([pixel[2 * i], pixel[2 * i + 1]])))
+            })
+            .collect()
+    } else if format == TextureFormat::Rgba32Float {
+        src.data
+            .chunks_exact(16)
+            .map(|pixel| {
+                [0, 1, 2, 3].map(|i| {
+                    f32::from_le_bytes([
+                        pixel[4 * i],
+                        pixel[4 * i + 1],
+                        pixel[4 * i + 2],
+                        pixel[4 * i + 3],
+                    ])
+                })
+            })
+            .collect()
+    } else {
+        return None;
+    };
+    Some(pixels)
+}
+
+/// Encodes linear RGBA values into the pixel data of `format`, applying ordered dithering to
+/// 8-bit formats if the row width is given.
+fn encode(
+    pixels: &[[f32; 4]],
+    format: TextureFormat,
+    dither_width: Option<usize>,
+) -> Option<Vec<u8>> {
+    let data = if is_rgba8(format) {
+        pixels
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &pixel)| {
+                let mut rgba = pixel;
+                if format.is_srgb() {
+                    for c in &mut rgba[..3] {
+                        *c = linear_to_srgb(*c);
+                    }
+                }
+                if is_bgra8(format) {
+                    rgba.swap(0, 2);
+                }
+                match dither_width.filter(|&width| width > 0) {
+                    Some(width) => {
+                        let offset = bayer_offset(i % width, i / width);
+                        rgba.map(|c| unorm8(c + offset))
+                    }
+                    None => rgba.map(unorm8),
+                }
+            })
+            .collect()
+    } else {
+        match format {
+            TextureFormat::Rgba32Float => pixels
+                .iter()
+                .flat_map(|pixel| pixel.iter().flat_map(|c| c.to_le_bytes()))
+                .collect(),
+            TextureFormat::R32Float => pixels
+                .iter()
+                .flat_map(|pixel| pixel[0].to_le_bytes())
+                .collect(),
+            TextureFormat::R8Unorm => pixels.iter().map(|pixel| unorm8(pixel[0])).collect(),
+            _ => return None,
+        }
+    };
+    Some(data)
+}
+
+/// Offset in `[-0.5, 0.5)` 8-bit steps of the 8x8 Bayer matrix at a pixel.
+fn bayer_offset(x: usize, y: usize) -> f32 {
+    // Interleaves the bits of x ^ y and y to get the matrix entry without storing it.
+    let (x, y) = (x & 7, y & 7);
+    let xor = x ^ y;
+    let index = ((xor & 1) << 5)
+        | ((y & 1) << 4)
+        | ((xor & 2) << 2)
+        | ((y & 2) << 1)
+        | ((xor & 4) >> 1)
+        | ((y & 4) >> 2);
+    (index as f32 + 0.5) / 64.0 / 255.0 - 0.5 / 255.0
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    use super::*;
+
+    fn image(width: u32, height: u32, format: TextureFormat, data: Vec<u8>) -> Image {
+        Image::new(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        )
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn converts_between_8_bit_formats() {
+        let src = image(1, 1, TextureFormat::Rgba8UnormSrgb, vec![1, 2, 3, 4]);
+        let bgra = convert_export_image(&src, TextureFormat::Bgra8UnormSrgb).unwrap();
+        assert_eq!(bgra.data, [3, 2, 1, 4]);
+        assert_eq!(
+            bgra.texture_descriptor.format,
+            TextureFormat::Bgra8UnormSrgb
+        );
+        let one = 0x3c00u16.to_le_bytes();
+        let half = image(1, 1, TextureFormat::Rgba16Float, one.repeat(4));
+        let unorm = convert_export_image(&half, TextureFormat::Rgba8Unorm).unwrap();
+        assert_eq!(unorm.data, [255; 4]);
+        assert!(convert_export_image(&src, TextureFormat::Depth32Float).is_none());
+    }
+
+    #[test]
+    fn ordered_dithering_preserves_the_mean() {
+        // A quarter step above 100 in every channel of an 8x8 block.
+        let value = 100.25 / 255.0;
+        let src = image(
+            8,
+            8,
+            TextureFormat::Rgba32Float,
+            floats(&[value; 8 * 8 * 4]),
+        );
+
+        let rounded =
+            convert_export_image_dithered(&src, TextureFormat::Rgba8Unorm, Dithering::None)
+                .unwrap();
+        assert!(rounded.data.iter().all(|&v| v == 100));
+
+        let dithered =
+            convert_export_image_dithered(&src, TextureFormat::Rgba8Unorm, Dithering::Ordered)
+                .unwrap();
+        assert!(dithered.data.iter().all(|&v| v == 100 || v == 101));
+        let sum: u32 = dithered.data.iter().map(|&v| v as u32).sum();
+        assert_eq!(sum as f32 / dithered.data.len() as f32, 100.25);
+    }
+
+    #[test]
+    fn bayer_offsets_are_distinct_and_centered() {
+        let mut offsets: Vec<f32> = (0..64).map(|i| bayer_offset(i % 8, i / 8)).collect();
+        assert!(offsets.iter().all(|offset| offset.abs() < 0.5 / 255.0));
+        assert!(offsets.iter().sum::<f32>().abs() < 1e-6);
+        offsets.sort_by(f32::total_cmp);
+        offsets.dedup();
+        assert_eq!(offsets.len(), 64);
+        assert_eq!(bayer_offset(8, 8), bayer_offset(0, 0));
+    }
+
+    #[test]
+    fn encodes_yuv420() {
+        let red = image(
+            2,
+            2,
+            TextureFormat::Rgba8UnormSrgb,
+            [255, 0, 0, 255].repeat(4),
+        );
+        let nv12 = encode_yuv420(&red, YuvFormat::default()).unwrap();
+        assert_eq!(nv12, [63, 63, 63, 63, 102, 240]);
+        let bt601 = YuvFormat {
+            matrix: YuvMatrix::Bt601,
+            ..default()
+        };
+        assert_eq!(encode_yuv420(&red, bt601).unwrap()[0], 81);
+
+        // Odd sizes round the chroma planes up.
+        let white = image(3, 3, TextureFormat::Bgra8Unorm, [255; 4].repeat(9));
+        let i420 = YuvFormat {
+            layout: Yuv420Layout::I420,
+            ..default()
+        };
+        let data = encode_yuv420(&white, i420).unwrap();
+        assert_eq!(data.len(), 9 + 2 * 4);
+        assert!(data[..9].iter().all(|&y| y == 235));
+        assert!(data[9..].iter().all(|&c| c == 128));
+    }
+
+    #[test]
+    fn colormap_normalizes_values() {
+        let depth = image(3, 1, TextureFormat::R32Float, floats(&[2.0, 4.0, f32::NAN]));
+        let turbo = apply_colormap(&depth, Colormap::Turbo).unwrap();
+        let lut = Colormap::Turbo.lut();
+        assert_eq!(
+            turbo.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(turbo.data[..4], [lut[0][0], lut[0][1], lut[0][2], 255]);
+        assert_eq!(
+            turbo.data[4..8],
+            [lut[255][0], lut[255][1], lut[255][2], 255]
+        );
+        assert_eq!(turbo.data[8..], [0, 0, 0, 255]);
+
+        let viridis = Colormap::Viridis.lut();
+        let close = |a: [u8; 3], b: [u8; 3]| a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 4);
+        assert!(close(viridis[0], [68, 1, 84]));
+        assert!(close(viridis[255], [253, 231, 37]));
+
+        let stencil = image(1, 1, TextureFormat::Stencil8, vec![3]);
+        assert!(apply_colormap(&stencil, Colormap::Turbo).is_some());
+        let color = image(1, 1, TextureFormat::Rgba8Unorm, vec![0; 4]);
+        assert!(apply_colormap(&color, Colormap::Turbo).is_none());
+    }
+
+    #[test]
+    fn linearizes_reversed_depth() {
+        let projection = Mat4::perspective_infinite_reverse_rh(1.0, 1.0, 0.1);
+        let depth = image(3, 1, TextureFormat::Depth32Float, floats(&[0.1, 0.01, 0.0]));
+        let linear = linearize_depth(&depth, projection).unwrap();
+        assert_eq!(linear.texture_descriptor.format, TextureFormat::R32Float);
+        let distances = decode_single_channel(&linear).unwrap();
+        assert!((distances[0] - 1.0).abs() < 1e-4);
+        assert!((distances[1] - 10.0).abs() < 1e-3);
+        assert_eq!(distances[2], f32::INFINITY);
+
+        let stencil = image(1, 1, TextureFormat::Stencil8, vec![0]);
+        assert!(linearize_depth(&stencil, projection).is_none());
+    }
+
+    #[test]
+    fn encodes_signed_normals() {
+        let normals = image(
+            1,
+            1,
+            TextureFormat::Rgba32Float,
+            floats(&[-1.0, 0.0, 1.0, 0.0]),
+        );
+        let encoded = encode_normals(&normals).unwrap();
+        assert_eq!(encoded.texture_descriptor.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(encoded.data, [0, 128, 255, 255]);
+    }
+
+    #[test]
+    fn decodes_half_floats() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+}
@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+
+use ddsfile::{AlphaMode, D3D10ResourceDimension, Dds, DxgiFormat, NewDxgiParams};
+use image::{imageops, DynamicImage, RgbaImage};
+use intel_tex_2::{bc1, bc3, bc7, RgbaSurface};
+
+/// Block compression format of frames exported as DDS.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BcFormat {
+    /// RGB with 1 bit alpha, 4 bits per pixel.
+    Bc1,
+    /// RGBA, 8 bits per pixel.
+    Bc3,
+    /// RGBA with the best quality, 8 bits per pixel.
+    #[default]
+    Bc7,
+}
+
+/// Compresses a frame on the CPU and wraps it in a DDS container.
+///
+/// Frames whose size isn't a multiple of the 4x4 block size are padded by repeating their last
+/// row and column; the DDS header keeps the original size.
+pub(crate) fn encode_dds(image: &DynamicImage, format: BcFormat) -> Result<Vec<u8>, String> {
+    let (width, height) = (image.width(), image.height());
+    let rgba = image.to_rgba8();
+    let padded = pad_to_blocks(&rgba);
+    let surface = RgbaSurface {
+        data: padded.as_raw(),
+        width: padded.width(),
+        height: padded.height(),
+        stride: padded.width() * 4,
+    };
+    let (blocks, dxgi_format) = match format {
+        BcFormat::Bc1 => (bc1::compress_blocks(&surface), DxgiFormat::BC1_UNorm_sRGB),
+        BcFormat::Bc3 => (bc3::compress_blocks(&surface), DxgiFormat::BC3_UNorm_sRGB),
+        BcFormat::Bc7 => (
+            bc7::compress_blocks(&bc7::alpha_basic_settings(), &surface),
+            DxgiFormat::BC7_UNorm_sRGB,
+        ),
+    };
+
+    let mut dds = Dds::new_dxgi(NewDxgiParams {
+        height,
+        width,
+        depth: None,
+        format: dxgi_format,
+        mipmap_levels: None,
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: D3D10ResourceDimension::Texture2D,
+        alpha_mode: AlphaMode::Straight,
+    })
+    .map_err(|e| e.to_string())?;
+    dds.data = blocks;
+    let mut bytes = Vec::new();
+    dds.write(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+fn pad_to_blocks(image: &RgbaImage) -> Cow<'_, RgbaImage> {
+    let (width, height) = image.dimensions();
+    let (padded_width, padded_height) = (width.div_ceil(4) * 4, height.div_ceil(4) * 4);
+    if (width, height) == (padded_width, padded_height) {
+        return Cow::Borrowed(image);
+    }
+    let mut padded = RgbaImage::new(padded_width, padded_height);
+    imageops::replace(&mut padded, image, 0, 0);
+    for y in 0..padded_height {
+        for x in 0..padded_width {
+            if x >= width || y >= height {
+                let pixel = *image.get_pixel(x.min(width - 1), y.min(height - 1));
+                padded.put_pixel(x, y, pixel);
+            }
+        }
+    }
+    Cow::Owned(padded)
+}
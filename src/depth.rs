@@ -0,0 +1,64 @@
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{camera::RenderTarget, extract_component::ExtractComponent},
+};
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::ImageExportSource;
+
+/// Projection matrix of the camera rendering into the export's source, captured in the main
+/// world for [`ImageExportSettings::linearize_depth`].
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct ExportProjection(pub Mat4);
+
+impl ExtractComponent for ExportProjection {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(*item)
+    }
+}
+
+/// Keeps [`ExportProjection`] up to date on exports linearizing depth, taken from the camera
+/// the source is captured after, or else the first one rendering into the source's image.
+pub(crate) fn update_export_projections(
+    mut commands: Commands,
+    exports: Query<(
+        Entity,
+        &Handle<ImageExportSource>,
+        &ImageExportSettings,
+        Has<ExportProjection>,
+    )>,
+    sources: Res<Assets<ImageExportSource>>,
+    cameras: Query<&Camera>,
+) {
+    for (entity, source_handle, settings, has_projection) in &exports {
+        if !settings.linearize_depth {
+            if has_projection {
+                commands.entity(entity).remove::<ExportProjection>();
+            }
+            continue;
+        }
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        let camera = match source.capture_after_camera() {
+            Some(camera) => cameras.get(camera).ok(),
+            None => {
+                let image = source.image_handle();
+                cameras
+                    .iter()
+                    .find(|camera| matches!(&camera.target, RenderTarget::Image(i) if i == image))
+            }
+        };
+        let Some(camera) = camera else {
+            continue;
+        };
+        commands
+            .entity(entity)
+            .insert(ExportProjection(camera.projection_matrix()));
+    }
+}
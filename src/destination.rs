@@ -0,0 +1,175 @@
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent, render_asset::RenderAssets, render_resource::Extent3d,
+        Extract,
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::control::GlobalExportControl;
+use crate::events::{ExportMessage, ExportMessageSender};
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+/// Copies every exported frame into a main world image, e.g. to show a live preview of an
+/// off-screen camera in the UI.
+///
+/// The destination image is resized and reformatted to match the source if necessary. Its
+/// `TextureUsages` are left untouched.
+#[derive(Component, Clone)]
+pub struct ImageExportDestination(pub Handle<Image>);
+
+impl ExtractComponent for ImageExportDestination {
+    type Query = (
+        &'static ImageExportDestination,
+        &'static Handle<ImageExportSource>,
+    );
+    type Filter = ();
+    type Out = (
+        ImageExportDestination,
+        Handle<ImageExportSource>,
+        StreamingSink,
+    );
+
+    fn extract_component(
+        (destination, source_handle): QueryItem<'_, Self::Query>,
+    ) -> Option<Self::Out> {
+        Some((
+            ImageExportDestination(destination.0.clone()),
+            source_handle.clone(),
+            StreamingSink,
+        ))
+    }
+}
+
+#[derive(Bundle)]
+pub struct ImageExportDestinationBundle {
+    pub source: Handle<ImageExportSource>,
+    pub destination: ImageExportDestination,
+}
+
+/// Copies every frame of the source into an image on the GPU, e.g. to feed the output of one
+/// camera into the material or render target of another without reading it back to the CPU.
+///
+/// The copy is made by the export node right after the frame is captured, so an entity with
+/// only this component never maps a readback buffer. The destination image is resized and
+/// reformatted to match the source if necessary and needs `TextureUsages::COPY_DST`, which
+/// images have by default.
+#[derive(Component, Clone)]
+pub struct ImageExportGpuDestination(pub Handle<Image>);
+
+#[derive(Bundle)]
+pub struct ImageExportGpuDestinationBundle {
+    pub source: Handle<ImageExportSource>,
+    pub destination: ImageExportGpuDestination,
+}
+
+/// The GPU destinations of every source, copied into by the export nodes.
+#[derive(Resource, Default)]
+pub(crate) struct GpuDestinations(pub HashMap<AssetId<ImageExportSource>, Vec<AssetId<Image>>>);
+
+/// Extracts the GPU destinations, leaving none while the exports are
+/// [paused](GlobalExportControl), so that the export nodes don't copy into them.
+pub(crate) fn extract_gpu_destinations(
+    destinations: Extract<Query<(&ImageExportGpuDestination, &Handle<ImageExportSource>)>>,
+    control: Extract<Res<GlobalExportControl>>,
+    mut gpu_destinations: ResMut<GpuDestinations>,
+) {
+    gpu_destinations.0.clear();
+    if control.paused {
+        return;
+    }
+    for (destination, source_handle) in &destinations {
+        gpu_destinations
+            .0
+            .entry(source_handle.id())
+            .or_default()
+            .push(destination.0.id());
+    }
+}
+
+/// Keeps the size and format of GPU destinations in sync with their sources, so that the
+/// texture copy in the export node is valid.
+pub(crate) fn resize_gpu_destinations(
+    destinations: Query<(&ImageExportGpuDestination, &Handle<ImageExportSource>)>,
+    sources: Res<Assets<ImageExportSource>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (destination, source_handle) in &destinations {
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        let Some(image) = images.get(source.image_handle()) else {
+            continue;
+        };
+        let size = source.preview().unwrap_or_else(|| image.size());
+        let format = image.texture_descriptor.format;
+        let Some(target) = images.get(&destination.0) else {
+            continue;
+        };
+        if target.size() == size && target.texture_descriptor.format == format {
+            continue;
+        }
+        debug!(
+            "reallocating image export GPU destination {:?} to {size} {format:?}",
+            destination.0.id()
+        );
+        let target = images.get_mut(&destination.0).unwrap();
+        target.texture_descriptor.format = format;
+        target.resize(Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        });
+    }
+}
+
+pub(crate) fn copy_to_destinations(
+    destinations: Query<(Entity, &ImageExportDestination, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    sender: Res<ExportMessageSender>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    let sources = sources.into_inner();
+    let images = images.into_inner();
+
+    for (entity, destination, source_handle) in &destinations {
+        if !source_is_present(source_handle.id(), sources, images, &mut missing_sources) {
+            continue;
+        }
+        if let Some(frame) = frames.get_export_shared(entity, source_handle.id()) {
+            sender.send(ExportMessage::DestinationFrame {
+                destination: destination.0.id(),
+                frame,
+            });
+        }
+    }
+}
+
+pub(crate) fn write_destination_frame(
+    images: &mut Assets<Image>,
+    id: AssetId<Image>,
+    frame: &Image,
+) {
+    let Some(image) = images.get_mut(id) else {
+        return;
+    };
+    let descriptor = &mut image.texture_descriptor;
+    if descriptor.size != frame.texture_descriptor.size
+        || descriptor.format != frame.texture_descriptor.format
+    {
+        debug!(
+            "reallocating image export destination {id:?} to {:?} {:?}",
+            frame.texture_descriptor.size, frame.texture_descriptor.format
+        );
+        descriptor.size = frame.texture_descriptor.size;
+        descriptor.format = frame.texture_descriptor.format;
+    }
+    // Reuses the destination's data instead of taking the frame, which is shared with the
+    // other sinks.
+    image.data.clear();
+    image.data.extend_from_slice(&frame.data);
+}
@@ -0,0 +1,1410 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    core::FrameCount,
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{Extent3d, TextureFormat},
+    },
+    utils::{HashMap, HashSet},
+};
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageFormat};
+
+use crate::adaptive::AdaptiveQuality;
+#[cfg(feature = "burn_in")]
+use crate::burn_in::BurnInSettings;
+use crate::capture::CaptureMode;
+use crate::control::GlobalExportControl;
+use crate::convert::{
+    apply_colormap, convert_export_image_dithered, encode_normals, linearize_depth, Colormap,
+    Dithering, ExportChannelMode,
+};
+#[cfg(feature = "dds")]
+use crate::dds::{encode_dds, BcFormat};
+use crate::depth::ExportProjection;
+use crate::events::{ExportFinishedReason, ExportMessage, ExportMessageSender};
+use crate::exposure::AutoExposure;
+use crate::motion::{changed_fraction, motion_thumbnail};
+#[cfg(feature = "png")]
+use crate::parallel_png::encode_png_bands;
+use crate::plugin::{source_is_present, ExportedFrames, ImageExport, ImageExportSource};
+use crate::rate::RateLimiter;
+use crate::resize::ResizeSettings;
+use crate::sidecar::{FrameSidecar, SidecarImage, SidecarSettings};
+use crate::timing::TimingLog;
+use crate::viewports::ExportViewports;
+
+/// What an export does with black frames at its start, see
+/// [`ImageExportSettings::first_frame_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FirstFramePolicy {
+    /// Writes every frame.
+    Capture,
+    /// Skips frames whose bytes are all zero until the first frame with content, but at most
+    /// `limit` frames.
+    SkipBlack { limit: u32 },
+    /// Writes every frame, warning if the first one is black.
+    #[default]
+    WarnOnly,
+}
+
+/// Settings controlling where and how an export writes its frames to disk.
+#[derive(Component, Clone, Debug)]
+pub struct ImageExportSettings {
+    /// Frames are saved to `[output_dir]/[#####].[extension]`, relative to the directory passed
+    /// to [`ImageExportPlugin::disk`](crate::ImageExportPlugin::disk).
+    pub output_dir: String,
+    /// Determines the image format, e.g. "png", or "exr" for HDR renders.
+    pub extension: String,
+    /// Selects the image format instead of `extension`. An `extension` of another format is
+    /// replaced with the format's extension, with a warning.
+    pub format: Option<ImageExportFormat>,
+    /// Replaces the `[#####]` frame number in file (and split frame directory) names.
+    pub frame_name: Option<FrameNameFormatter>,
+    /// Groups consecutive frames into clips, each saved to its own directory.
+    pub clip: Option<ClipExport>,
+    /// Block compression used for the "dds" extension.
+    #[cfg(feature = "dds")]
+    pub bc_format: BcFormat,
+    /// Stops the export before the total size of written frames exceeds this many bytes.
+    pub max_output_bytes: Option<u64>,
+    /// Keeps only the most recently written frames on disk, deleting the oldest ones (and
+    /// their sidecars) beyond this count.
+    pub max_files: Option<usize>,
+    pub mode: CaptureMode,
+    /// Writes at most this many frames per wall-clock second, e.g. 24 for film while
+    /// rendering at 144fps. Frames in between are neither read back nor encoded. Rates that
+    /// aren't positive and finite are ignored with a warning.
+    pub max_fps: Option<f64>,
+    pub channel_mode: ExportChannelMode,
+    /// Converts single-channel depth frames into view-space distances in world units, using
+    /// the projection of the camera rendering into the source, see
+    /// [`linearize_depth`](crate::linearize_depth). Write them as "exr" to keep the distances,
+    /// or combine with `colormap` for other formats.
+    pub linearize_depth: bool,
+    /// Writes the alpha channel. Disable it for opaque renders, e.g. to get smaller RGB PNGs
+    /// without a meaningless alpha channel.
+    pub include_alpha: bool,
+    /// Visualizes single-channel sources, e.g. depth, through this colormap. Frames of other
+    /// formats are skipped.
+    pub colormap: Option<Colormap>,
+    /// Dithering applied when float frames are reduced to 8-bit sRGB for formats other than
+    /// "exr".
+    pub dithering: Dithering,
+    /// Rotates frames clockwise, e.g. for portrait displays showing a landscape render. The
+    /// width and height of the frames are swapped for quarter turns; `resize` refers to the
+    /// rotated size.
+    pub rotate: Rotation,
+    /// Resizes frames before they are encoded.
+    pub resize: Option<ResizeSettings>,
+    /// Scales frames relative to the source size before they are encoded, rounding to even
+    /// dimensions. Ignored if `resize` is set.
+    pub scale: f32,
+    /// Writes every frame in each of these sizes, e.g. 1080p, 720p and 360p for adaptive web
+    /// delivery, to `[output_dir]/[#####]/[width]x[height].[extension]`. The sizes are applied
+    /// after `resize` or `scale`, from the largest to the smallest, each resized from the
+    /// previous one. Only `width` and `height` are used.
+    pub renditions: Vec<Extent3d>,
+    /// Lowers `scale` while the export can't keep up with a target frame rate.
+    pub adaptive_quality: Option<AdaptiveQuality>,
+    /// Renders at this many times the source image's size and scales the frames back down
+    /// before they are written, anti-aliasing stills beyond what MSAA achieves. While
+    /// supersampling, the cameras rendering into the source image render into a private,
+    /// enlarged image instead, and the export's source handle is replaced by a source reading
+    /// it back. The source image keeps its size. 1 disables supersampling.
+    ///
+    /// Only frames written to disk are scaled back down; other sinks of the export receive
+    /// the enlarged frames. The factor is lowered, with a warning, if the enlarged image would
+    /// exceed the device's maximum texture size.
+    pub supersample: u32,
+    /// Normalizes the brightness of every frame before it's written. Off by default, as it
+    /// alters the rendered values.
+    pub auto_exposure: Option<AutoExposure>,
+    /// Compresses PNG frames in this many bands of rows in parallel, to speed up huge stills.
+    /// Frames are still saved to a single file with the same pixels, which is slightly larger
+    /// than a serially encoded one. Other formats are encoded serially.
+    pub encode_bands: Option<u32>,
+    /// Saves the viewport of every camera rendering into the source separately, e.g. for split
+    /// screen, to `[output_dir]/[#####]/[viewport].[extension]`. Viewports are numbered in
+    /// camera order; cameras without a viewport are skipped.
+    pub split_viewports: bool,
+    /// Starts a new subdirectory of `output_dir`, named after the UTC time the period started
+    /// at (e.g. `2024-06-01T12-00`), every time this period elapses. Frame numbers restart at 0
+    /// in every subdirectory.
+    pub rotate_every: Option<Duration>,
+    /// Skips frames unless more than this fraction of their pixels changed since the last
+    /// written frame, compared on a downscaled grayscale version. 0 writes every frame.
+    pub motion_threshold: f32,
+    /// Only logs the path, format and size of every frame that would be written, without
+    /// reading the frame back from the GPU or writing anything.
+    pub dry_run: bool,
+    /// Logs every frame of this export, with its path, size and timings, at the info level
+    /// instead of the trace level, to debug one export among many.
+    pub verbose: bool,
+    /// Exports sharing a capture group only write when all of them have a frame of the same
+    /// render frame, so a stalled member holds back the others. Their frames share one frame
+    /// number.
+    pub capture_group: Option<u32>,
+    /// Exports with lower values are written first within a frame. Ties are broken by entity.
+    pub priority: i32,
+    /// Handles black frames at the start of the export, which are read back when the source
+    /// wasn't rendered into yet.
+    pub first_frame_policy: FirstFramePolicy,
+    /// First render frame, as counted by [`FrameCount`], that is captured.
+    pub start_frame: Option<u32>,
+    /// Last render frame, as counted by [`FrameCount`], that is captured. The export finishes
+    /// afterwards.
+    pub end_frame: Option<u32>,
+    /// Writes a JSON file with the camera's exposure settings next to every frame.
+    pub sidecar: Option<SidecarSettings>,
+    /// Replaces this file, relative to the directory passed to
+    /// [`ImageExportPlugin::disk`](crate::ImageExportPlugin::disk), with a `[frame] [path]`
+    /// line after every written frame and its sidecar, so that external processes can watch
+    /// it for new frames. The file is replaced atomically, it never holds a partial line.
+    pub signal_file: Option<PathBuf>,
+    /// Reads every written file back and compares it to the encoded frame, sending a
+    /// [`FrameVerificationFailed`](crate::FrameVerificationFailed) event on a mismatch, e.g.
+    /// to catch silent corruption on network storage. Doubles the IO.
+    pub verify_writes: bool,
+    /// Text drawn into the corner of every written frame. Only the files written to disk get the
+    /// text, the frames the other sinks see are left untouched.
+    #[cfg(feature = "burn_in")]
+    pub burn_in: Option<BurnInSettings>,
+    /// Opens every written frame in the default image viewer. Meant for
+    /// [`CaptureMode::OnRequest`], as a continuous export would open every frame.
+    #[cfg(feature = "open")]
+    pub open_after_write: bool,
+}
+
+impl Default for ImageExportSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: "out".into(),
+            extension: "png".into(),
+            format: None,
+            frame_name: None,
+            clip: None,
+            #[cfg(feature = "dds")]
+            bc_format: BcFormat::default(),
+            max_output_bytes: None,
+            max_files: None,
+            mode: CaptureMode::default(),
+            max_fps: None,
+            channel_mode: ExportChannelMode::default(),
+            linearize_depth: false,
+            include_alpha: true,
+            colormap: None,
+            dithering: Dithering::default(),
+            rotate: Rotation::default(),
+            resize: None,
+            scale: 1.0,
+            renditions: Vec::new(),
+            adaptive_quality: None,
+            supersample: 1,
+            auto_exposure: None,
+            encode_bands: None,
+            split_viewports: false,
+            rotate_every: None,
+            motion_threshold: 0.0,
+            dry_run: false,
+            verbose: false,
+            capture_group: None,
+            priority: 0,
+            first_frame_policy: FirstFramePolicy::default(),
+            start_frame: None,
+            end_frame: None,
+            sidecar: None,
+            signal_file: None,
+            verify_writes: false,
+            #[cfg(feature = "burn_in")]
+            burn_in: None,
+            #[cfg(feature = "open")]
+            open_after_write: false,
+        }
+    }
+}
+
+impl ImageExportSettings {
+    /// How rotated frames of `width` x `height` are resized, before any renditions.
+    fn resize_for(&self, width: u32, height: u32) -> Option<ResizeSettings> {
+        self.resize.or_else(|| {
+            (self.scale != 1.0).then(|| ResizeSettings::scaled(width, height, self.scale))
+        })
+    }
+
+    /// Size of the written frame, before any renditions, for a supersampled frame of `width` x
+    /// `height`.
+    fn output_size(&self, width: u32, height: u32) -> UVec2 {
+        let (width, height) = if self.rotate.swaps_axes() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        self.resize_for(width, height)
+            .map_or(UVec2::new(width, height), |resize| {
+                UVec2::new(resize.width, resize.height)
+            })
+    }
+
+    /// The time between written frames if `max_fps` is a valid rate.
+    fn frame_interval(&self) -> Option<Duration> {
+        self.max_fps
+            .filter(|max_fps| max_fps.is_finite() && *max_fps > 0.0)
+            .and_then(|max_fps| Duration::try_from_secs_f64(1.0 / max_fps).ok())
+    }
+}
+
+/// Groups every `frames` consecutive frames of an export into a clip, e.g. as training samples
+/// for temporal models.
+///
+/// Frames are saved to `[output_dir]/clip_[#####]/[##].[extension]`, numbered from 0 within
+/// their clip, and the next clip starts once a clip is full. The last clip of an export that
+/// stops early may hold fewer frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClipExport {
+    pub frames: usize,
+}
+
+/// Clockwise rotation of exported frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Rotation::None => image,
+            Rotation::Clockwise90 => image.rotate90(),
+            Rotation::Clockwise180 => image.rotate180(),
+            Rotation::Clockwise270 => image.rotate270(),
+        }
+    }
+
+    /// Whether the rotation swaps the width and height of frames.
+    pub(crate) fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Clockwise270)
+    }
+}
+
+/// Image format of the frames written to disk, see [`ImageExportSettings::format`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageExportFormat {
+    Png,
+    Jpeg,
+    /// OpenEXR, keeping the full range of HDR renders.
+    Exr,
+    /// Block compressed DDS, see [`ImageExportSettings::bc_format`].
+    #[cfg(feature = "dds")]
+    Dds,
+}
+
+impl ImageExportFormat {
+    /// The extension frames of this format are saved with.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageExportFormat::Png => "png",
+            ImageExportFormat::Jpeg => "jpg",
+            ImageExportFormat::Exr => "exr",
+            #[cfg(feature = "dds")]
+            ImageExportFormat::Dds => "dds",
+        }
+    }
+
+    /// Whether `extension` denotes this format, e.g. "jpeg" for [`ImageExportFormat::Jpeg`].
+    pub fn matches_extension(self, extension: &str) -> bool {
+        let format = match self {
+            ImageExportFormat::Png => ImageFormat::Png,
+            ImageExportFormat::Jpeg => ImageFormat::Jpeg,
+            ImageExportFormat::Exr => ImageFormat::OpenExr,
+            #[cfg(feature = "dds")]
+            ImageExportFormat::Dds => ImageFormat::Dds,
+        };
+        ImageFormat::from_extension(extension) == Some(format)
+    }
+}
+
+/// Sets the `extension` of exports with a `format` to match it, and warns about frame names
+/// ending in the extension of another image format, which is replaced when the frames are
+/// saved, and about invalid `max_fps` rates.
+pub(crate) fn resolve_export_extensions(
+    mut exports: Query<(Entity, &mut ImageExportSettings), Changed<ImageExportSettings>>,
+) {
+    for (entity, mut settings) in &mut exports {
+        if let (Some(max_fps), None) = (settings.max_fps, settings.frame_interval()) {
+            warn!("export {entity:?} has an invalid max_fps of {max_fps}, writing every frame");
+        }
+        if let Some(format) = settings.format {
+            if !format.matches_extension(&settings.extension) {
+                warn!(
+                    "export {entity:?} writes {format:?} frames, replacing the extension \"{}\" with \"{}\"",
+                    settings.extension,
+                    format.extension()
+                );
+                settings.extension = format.extension().into();
+            }
+        }
+        if let Some(formatter) = &settings.frame_name {
+            let name = (formatter.0)(0);
+            if let Some(extension) = image_extension(&name) {
+                if !extension.eq_ignore_ascii_case(&settings.extension) {
+                    warn!(
+                        "frame names of export {entity:?} end in \".{extension}\", the frames are saved as \".{}\" files instead",
+                        settings.extension
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The extension of `name` if it denotes an image format.
+fn image_extension(name: &str) -> Option<&str> {
+    let (_, extension) = name.rsplit_once('.')?;
+    ImageFormat::from_extension(extension).map(|_| extension)
+}
+
+/// Turns a frame number into the name a frame is saved under, without the extension, e.g.
+/// `shot010_v002.0042` for VFX pipelines.
+#[derive(Clone)]
+pub struct FrameNameFormatter(pub Arc<dyn Fn(u32) -> String + Send + Sync>);
+
+impl FrameNameFormatter {
+    pub fn new(format: impl Fn(u32) -> String + Send + Sync + 'static) -> Self {
+        Self(Arc::new(format))
+    }
+}
+
+impl std::fmt::Debug for FrameNameFormatter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FrameNameFormatter")
+    }
+}
+
+/// Encodes a frame into the image format matching `extension`.
+pub(crate) fn encode_image(image: &DynamicImage, extension: &str) -> Result<Vec<u8>, String> {
+    let format = ImageFormat::from_extension(extension)
+        .ok_or_else(|| format!("unsupported image export extension: {extension}"))?;
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Encodes a frame of a disk export, which also supports block compressed DDS.
+fn encode_disk_image(
+    settings: &ImageExportSettings,
+    image: &DynamicImage,
+) -> Result<Vec<u8>, String> {
+    #[cfg(feature = "dds")]
+    if settings.extension.eq_ignore_ascii_case("dds") {
+        return encode_dds(image, settings.bc_format);
+    }
+    #[cfg(feature = "png")]
+    if let Some(bands) = settings.encode_bands {
+        if ImageFormat::from_extension(&settings.extension) == Some(ImageFormat::Png) {
+            if let Some(png) = encode_png_bands(image, bands) {
+                return Ok(png);
+            }
+        }
+    }
+    encode_image(image, &settings.extension)
+}
+
+/// Converts a frame into a [`DynamicImage`] to be encoded as `extension`, along with whether its
+/// colors are sRGB encoded.
+///
+/// Image files store sRGB, so float and linear 8-bit colors are converted to 8-bit sRGB with
+/// `dithering` first. Only EXR files keep float colors.
+pub(crate) fn into_encodable(
+    image: Cow<Image>,
+    extension: &str,
+    dithering: Dithering,
+) -> Result<(DynamicImage, bool), String> {
+    let format = image.texture_descriptor.format;
+    let is_float = matches!(
+        format,
+        TextureFormat::Rgba16Float | TextureFormat::Rgba32Float
+    );
+    let is_linear_color = matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Bgra8Unorm
+    );
+    if (is_float && ImageFormat::from_extension(extension) != Some(ImageFormat::OpenExr))
+        || is_linear_color
+    {
+        let converted =
+            convert_export_image_dithered(&image, TextureFormat::Rgba8UnormSrgb, dithering)
+                .ok_or("failed to convert frame to 8-bit sRGB")?;
+        return into_dynamic(converted).map(|dynamic| (dynamic, true));
+    }
+    into_dynamic(image.into_owned()).map(|dynamic| (dynamic, format.is_srgb()))
+}
+
+/// Like [`Image::try_into_dynamic`], but also accepts linear `Rgba8Unorm`, `Rgba32Float` and
+/// `R32Float` images.
+pub(crate) fn into_dynamic(image: Image) -> Result<DynamicImage, String> {
+    let (width, height) = (image.width(), image.height());
+    let floats = || -> Vec<f32> { bytemuck::pod_collect_to_vec(&image.data) };
+    let converted = match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm => {
+            ImageBuffer::from_raw(width, height, image.data).map(DynamicImage::ImageRgba8)
+        }
+        TextureFormat::Rgba32Float => {
+            ImageBuffer::from_raw(width, height, floats()).map(DynamicImage::ImageRgba32F)
+        }
+        // Linearized depth, stored as gray RGB as there's no single-channel float image.
+        TextureFormat::R32Float => ImageBuffer::from_raw(
+            width,
+            height,
+            floats().into_iter().flat_map(|v| [v, v, v]).collect(),
+        )
+        .map(DynamicImage::ImageRgb32F),
+        _ => return image.try_into_dynamic().map_err(|e| e.to_string()),
+    };
+    converted.ok_or_else(|| "image data does not match its size".to_string())
+}
+
+/// Formats seconds since the Unix epoch as a UTC `YYYY-MM-DDTHH-MM` directory name.
+fn bucket_name(secs: u64) -> String {
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Converts days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}-{:02}",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60
+    )
+}
+
+/// Whether a frame is saved as a directory of files instead of a single file.
+fn is_split(settings: &ImageExportSettings) -> bool {
+    settings.split_viewports || !settings.renditions.is_empty()
+}
+
+/// Deletes a written frame, or the directory holding its files, along with its sidecar.
+fn remove_frame(path: &Path) {
+    trace!("removing old frame {}", path.display());
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    if let Err(e) = removed {
+        warn!("failed to remove old frame {}: {e}", path.display());
+    }
+    let _ = fs::remove_file(path.with_extension("json"));
+}
+
+/// Directory the `output_dir`s of disk exports are relative to. Disk exports are only written
+/// if this resource exists.
+#[derive(Resource)]
+pub(crate) struct DiskExportRoot(pub PathBuf);
+
+fn frame_path(
+    root: &DiskExportRoot,
+    settings: &ImageExportSettings,
+    state: &DiskExportState,
+) -> PathBuf {
+    let mut output_dir = root.0.join(&settings.output_dir);
+    if let Some((_, bucket)) = &state.bucket {
+        output_dir.push(bucket);
+    }
+    let mut frame = state.frame;
+    let mut name = None;
+    if let Some(clip) = settings.clip {
+        let frames = clip.frames.max(1) as u32;
+        output_dir.push(format!("clip_{:05}", frame / frames));
+        frame %= frames;
+        name = Some(format!("{frame:02}"));
+    }
+    let mut name = match &settings.frame_name {
+        Some(formatter) => (formatter.0)(frame),
+        None => name.unwrap_or_else(|| format!("{frame:05}")),
+    };
+    // Names are extended with the export's extension, so that a formatter adding its own, e.g.
+    // "0042.png" for a JPEG export, doesn't mislabel the file.
+    if let Some(extension) = image_extension(&name) {
+        name.truncate(name.len() - extension.len() - 1);
+    }
+    if is_split(settings) {
+        output_dir.join(name)
+    } else {
+        output_dir.join(format!("{name}.{}", settings.extension))
+    }
+}
+
+/// Drops the alpha channel of `image`, keeping its precision.
+fn without_alpha(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLumaA8(_) => image.to_luma8().into(),
+        DynamicImage::ImageLumaA16(_) => image.to_luma16().into(),
+        DynamicImage::ImageRgba8(_) => image.to_rgb8().into(),
+        DynamicImage::ImageRgba16(_) => image.to_rgb16().into(),
+        DynamicImage::ImageRgba32F(_) => image.to_rgb32f().into(),
+        image => image,
+    }
+}
+
+/// Crops, rotates, resizes, burns in and encodes a frame, resizing `srgb` frames in linear
+/// space, once per rendition if there are any. Returns the encoded files along with their
+/// names inside the frame's directory, or a single unnamed file if the frame isn't split.
+fn encode_frame(
+    settings: &ImageExportSettings,
+    dynamic: DynamicImage,
+    srgb: bool,
+    viewports: Option<&ExportViewports>,
+    frame: u32,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let parts: Vec<_> = match viewports.filter(|_| settings.split_viewports) {
+        Some(viewports) => viewports
+            .crop(&dynamic)
+            .into_iter()
+            .enumerate()
+            .map(|(i, part)| (i.to_string(), part))
+            .collect(),
+        None if settings.split_viewports => vec![("0".to_string(), dynamic)],
+        None => vec![(String::new(), dynamic)],
+    };
+
+    let join = |name: &str, suffix: String| {
+        if name.is_empty() {
+            suffix
+        } else {
+            format!("{name}_{suffix}")
+        }
+    };
+    let mut renditions: Vec<_> = settings
+        .renditions
+        .iter()
+        .map(|size| (size.width.max(1), size.height.max(1)))
+        .collect();
+    renditions.sort_by_key(|&(width, height)| std::cmp::Reverse(width as u64 * height as u64));
+    renditions.dedup();
+
+    let mut resized_parts = Vec::new();
+    for (name, part) in parts {
+        let mut part = settings.rotate.apply(part);
+        let resize = settings.resize_for(part.width(), part.height());
+        if let Some(resized) = resize.and_then(|resize| resize.apply(&part, srgb)) {
+            part = resized;
+        }
+        if renditions.is_empty() {
+            resized_parts.push((name, part));
+            continue;
+        }
+        let filter = settings
+            .resize
+            .map(|resize| resize.filter)
+            .unwrap_or_default();
+        for &(width, height) in &renditions {
+            let resize = ResizeSettings {
+                width,
+                height,
+                filter,
+            };
+            if let Some(resized) = resize.apply(&part, srgb) {
+                part = resized;
+            }
+            resized_parts.push((join(&name, format!("{width}x{height}")), part.clone()));
+        }
+    }
+
+    let mut encoded = Vec::new();
+    // The frame number is only drawn by the burn-in.
+    #[cfg(not(feature = "burn_in"))]
+    let _ = frame;
+    for (name, part) in resized_parts {
+        #[cfg(feature = "burn_in")]
+        let part = match &settings.burn_in {
+            Some(burn_in) => {
+                let mut part = part;
+                burn_in.apply(&mut part, frame);
+                part
+            }
+            None => part,
+        };
+        let part = if settings.include_alpha {
+            part
+        } else {
+            without_alpha(part)
+        };
+        encoded.push((name, encode_disk_image(settings, &part)?));
+    }
+    Ok(encoded)
+}
+
+/// The files of an encoded frame written to `path`, which is the directory holding them if the
+/// frame is split.
+fn frame_files<'a>(
+    settings: &ImageExportSettings,
+    path: &Path,
+    encoded: &'a [(String, Vec<u8>)],
+) -> Vec<(PathBuf, &'a [u8])> {
+    if !is_split(settings) {
+        return encoded
+            .first()
+            .map(|(_, bytes)| (path.to_path_buf(), bytes.as_slice()))
+            .into_iter()
+            .collect();
+    }
+    encoded
+        .iter()
+        .map(|(name, bytes)| {
+            (
+                path.join(format!("{name}.{}", settings.extension)),
+                bytes.as_slice(),
+            )
+        })
+        .collect()
+}
+
+/// Writes an encoded frame, or the directory holding its files, to `path`.
+fn write_frame(
+    settings: &ImageExportSettings,
+    path: &Path,
+    encoded: &[(String, Vec<u8>)],
+) -> std::io::Result<()> {
+    for (file, bytes) in frame_files(settings, path, encoded) {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(file, bytes)?;
+    }
+    Ok(())
+}
+
+/// Reads the files of a written frame back and compares them to what was written.
+fn verify_frame(
+    settings: &ImageExportSettings,
+    path: &Path,
+    encoded: &[(String, Vec<u8>)],
+) -> Result<(), String> {
+    for (file, bytes) in frame_files(settings, path, encoded) {
+        let written = fs::read(&file).map_err(|e| format!("failed to read back: {e}"))?;
+        if written.len() != bytes.len() {
+            return Err(format!(
+                "{} holds {} bytes instead of {}",
+                file.display(),
+                written.len(),
+                bytes.len()
+            ));
+        }
+        if let Some(offset) = written.iter().zip(bytes).position(|(a, b)| a != b) {
+            return Err(format!("{} differs at byte {offset}", file.display()));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct DiskExportState {
+    frame: u32,
+    handled_captures: u32,
+    handled_resets: u32,
+    /// Index and directory name of the current time bucket, if `rotate_every` is set.
+    bucket: Option<(u64, String)>,
+    /// Paths of the frames written so far, oldest first, if `max_files` is set.
+    written: VecDeque<PathBuf>,
+    /// Thumbnail of the last written frame, if `motion_threshold` is set.
+    motion_reference: Option<GrayImage>,
+    bytes_written: u64,
+    finished: bool,
+    /// Paces the frames if `max_fps` is set.
+    rate_limiter: Option<RateLimiter>,
+    /// Whether the rate limiter lets the current render frame through.
+    paced: bool,
+    /// Whether the first frame was checked against the `first_frame_policy`.
+    first_frame_checked: bool,
+    /// Black frames skipped at the start of the export.
+    skipped_black_frames: u32,
+}
+
+impl DiskExportState {
+    /// Advances to the next frame, handling a requested capture.
+    fn frame_written(&mut self, settings: &ImageExportSettings) {
+        self.frame += 1;
+        if settings.mode == CaptureMode::OnRequest {
+            self.handled_captures = self.handled_captures.wrapping_add(1);
+        }
+    }
+}
+
+/// Per-entity progress of the disk exports, kept in the render world.
+#[derive(Resource, Default)]
+pub(crate) struct DiskExportStates(HashMap<Entity, DiskExportState>);
+
+impl DiskExportStates {
+    /// Whether the export of `entity` writes a frame this frame and needs it read back.
+    pub(crate) fn wants_frame(
+        &self,
+        entity: Entity,
+        export: &ImageExport,
+        settings: &ImageExportSettings,
+        frame_count: u32,
+    ) -> bool {
+        let state = self.0.get(&entity);
+        let finished =
+            state.is_some_and(|state| state.finished && state.handled_resets == export.resets);
+        let handled_captures = state.map_or(0, |state| state.handled_captures);
+        let in_range = frame_count <= settings.end_frame.unwrap_or(u32::MAX);
+        let paced = settings.frame_interval().is_none() || state.is_some_and(|state| state.paced);
+        !finished
+            && paced
+            && !skips_frame(export, settings, frame_count)
+            && !settings.dry_run
+            && in_range
+            && (settings.mode == CaptureMode::Continuous
+                || handled_captures != export.requested_captures)
+    }
+}
+
+/// Whether the export skips the current frame because it's paused or didn't start yet. Checked
+/// again when frames are written, as other sinks may still read the frame back.
+fn skips_frame(export: &ImageExport, settings: &ImageExportSettings, frame_count: u32) -> bool {
+    export.paused
+        || settings
+            .start_frame
+            .is_some_and(|start| frame_count < start)
+}
+
+/// Decides which exports with a `max_fps` write the current frame, before frames are read
+/// back.
+pub(crate) fn pace_disk_exports(
+    exports: Query<(Entity, &ImageExportSettings)>,
+    mut states: ResMut<DiskExportStates>,
+) {
+    let now = Instant::now();
+    for (entity, settings) in &exports {
+        let Some(interval) = settings.frame_interval() else {
+            continue;
+        };
+        let state = states.0.entry(entity).or_default();
+        let rate_limiter = state
+            .rate_limiter
+            .get_or_insert_with(|| RateLimiter::new(interval));
+        // Restarts the schedule when `max_fps` changes.
+        if rate_limiter.interval() != interval {
+            *rate_limiter = RateLimiter::new(interval);
+        }
+        state.paced = rate_limiter.ready(now);
+    }
+}
+
+/// Logs a message about a single frame, at the info level if the export is verbose.
+macro_rules! frame_log {
+    ($settings:expr, $($arg:tt)+) => {
+        if $settings.verbose {
+            info!($($arg)+)
+        } else {
+            trace!($($arg)+)
+        }
+    };
+}
+
+/// Replaces the signal file with the number and path of the last written frame, renaming a
+/// temporary file over it.
+fn write_signal_file(signal_path: &Path, frame: u32, frame_path: &Path) -> std::io::Result<()> {
+    let mut temp_name = signal_path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+    fs::write(&temp_path, format!("{frame} {}\n", frame_path.display()))?;
+    fs::rename(&temp_path, signal_path)
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(crate) fn save_buffer_to_disk(
+    export_bundles: Query<(
+        Entity,
+        &ImageExport,
+        &Handle<ImageExportSource>,
+        &ImageExportSettings,
+        Option<&FrameSidecar>,
+        Option<&ExportViewports>,
+        Option<&ExportProjection>,
+    )>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    root: Res<DiskExportRoot>,
+    sender: Res<ExportMessageSender>,
+    frame_count: Res<FrameCount>,
+    mut states: ResMut<DiskExportStates>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+    mut started_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+    mut group_frames: Local<HashMap<u32, u32>>,
+    control: Res<GlobalExportControl>,
+    mut timing: Option<ResMut<TimingLog>>,
+) {
+    if control.paused {
+        return;
+    }
+    let sources = sources.into_inner();
+    let images = images.into_inner();
+
+    states
+        .0
+        .retain(|entity, _| export_bundles.contains(*entity));
+
+    let mut export_bundles: Vec<_> = export_bundles.iter().collect();
+    export_bundles.sort_by_key(|(entity, _, _, settings, ..)| (settings.priority, *entity));
+
+    let mut ready_groups = HashMap::<u32, bool>::new();
+    for (entity, _, source_handle, settings, ..) in &export_bundles {
+        if let Some(group) = settings.capture_group {
+            let ready = settings.dry_run
+                || states.0.get(entity).is_some_and(|state| state.finished)
+                || frames.get_export(*entity, source_handle.id()).is_some();
+            *ready_groups.entry(group).or_insert(true) &= ready;
+        }
+    }
+    let mut advanced_groups = HashSet::new();
+
+    for (entity, export, source_handle, settings, sidecar, viewports, projection) in export_bundles
+    {
+        let id = source_handle.id();
+        let state = states.0.entry(entity).or_default();
+        if state.handled_resets != export.resets {
+            info!(
+                "starting a new take of image source {id:?} in {}",
+                settings.output_dir
+            );
+            *state = DiskExportState {
+                handled_captures: state.handled_captures,
+                handled_resets: export.resets,
+                rate_limiter: state.rate_limiter.take(),
+                paced: state.paced,
+                ..default()
+            };
+        }
+        if state.finished {
+            continue;
+        }
+        if settings.end_frame.is_some_and(|end| frame_count.0 > end) {
+            info!(
+                "image export to {} reached its end frame after {} frames",
+                settings.output_dir, state.frame
+            );
+            state.finished = true;
+            sender.send(ExportMessage::Finished {
+                entity,
+                reason: ExportFinishedReason::EndFrameReached,
+            });
+            continue;
+        }
+        if skips_frame(export, settings, frame_count.0)
+            || !source_is_present(id, sources, images, &mut missing_sources)
+        {
+            continue;
+        }
+        if settings.frame_interval().is_some() && !state.paced {
+            continue;
+        }
+        if let Some(group) = settings.capture_group {
+            if !ready_groups[&group] {
+                frame_log!(
+                    settings,
+                    "capture group {group} is waiting for one of its exports, skipping frame"
+                );
+                continue;
+            }
+        }
+        // A requested capture is only handled once its frame is written, so that frames that
+        // fail to be read back, encoded or written retry it with the next frame.
+        if settings.mode == CaptureMode::OnRequest
+            && state.handled_captures == export.requested_captures
+        {
+            continue;
+        }
+        if let Some(rotate_every) = settings.rotate_every {
+            let period = rotate_every.as_secs().max(1);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let bucket = now.as_secs() / period;
+            if state.bucket.as_ref().map(|(index, _)| *index) != Some(bucket) {
+                let name = bucket_name(bucket * period);
+                info!("image export to {} rotated to {name}", settings.output_dir);
+                state.bucket = Some((bucket, name));
+                state.frame = 0;
+            }
+        }
+        if let Some(group) = settings.capture_group {
+            state.frame = *group_frames.entry(group).or_default();
+            advanced_groups.insert(group);
+        }
+        if settings.dry_run {
+            let path = frame_path(&root, settings, state);
+            let size = sources.get(id).map(|gpu_source| gpu_source.source_size);
+            info!(
+                "dry run: would write {:?} frame of size {:?} to {}",
+                ImageFormat::from_extension(&settings.extension),
+                size,
+                path.display()
+            );
+            state.frame_written(settings);
+            continue;
+        }
+        // Borrowed until the frame is converted or encoded, it's shared with the other sinks.
+        let Some(mut img) = frames.get_export_unpadded(entity, id) else {
+            frame_log!(settings, "no frame of image source {id:?} was read back");
+            if let Some(timing) = &mut timing {
+                timing.write(frame_count.0, entity, state.frame, None);
+            }
+            continue;
+        };
+        if !state.first_frame_checked {
+            // Sources that weren't rendered into yet are read back with all bytes zeroed.
+            let black = img.data.iter().all(|&byte| byte == 0);
+            match settings.first_frame_policy {
+                FirstFramePolicy::SkipBlack { limit } if black => {
+                    if state.skipped_black_frames < limit {
+                        state.skipped_black_frames += 1;
+                        frame_log!(settings, "skipping black frame of image source {id:?}");
+                        continue;
+                    }
+                    warn!("image source {id:?} is still black after skipping {limit} frames, writing its frames anyway");
+                }
+                FirstFramePolicy::WarnOnly if black => {
+                    warn!("the first frame of image source {id:?} is black, the source probably wasn't rendered into yet; use `FirstFramePolicy::SkipBlack` to skip such frames");
+                }
+                _ => {}
+            }
+            state.first_frame_checked = true;
+        }
+        let encode_start = Instant::now();
+        if started_sources.insert(id) {
+            info!(
+                "started exporting image source {id:?} to {}",
+                settings.output_dir
+            );
+        }
+
+        if settings.channel_mode == ExportChannelMode::NormalEncode {
+            match encode_normals(&img) {
+                Some(normals) => img = Cow::Owned(normals),
+                None => {
+                    error!("failed to encode normals of image source {id:?}");
+                    continue;
+                }
+            }
+        }
+
+        if settings.linearize_depth {
+            let Some(projection) = projection else {
+                frame_log!(
+                    settings,
+                    "no camera renders into image source {id:?}, skipping frame without a projection to linearize depth with"
+                );
+                continue;
+            };
+            match linearize_depth(&img, projection.0) {
+                Some(linear) => img = Cow::Owned(linear),
+                None => {
+                    error!(
+                        "failed to linearize depth of image source {id:?}, its {:?} format isn't a single-channel depth format",
+                        img.texture_descriptor.format
+                    );
+                    continue;
+                }
+            }
+        }
+
+        if let Some(colormap) = settings.colormap {
+            match apply_colormap(&img, colormap) {
+                Some(colorized) => img = Cow::Owned(colorized),
+                None => {
+                    error!(
+                        "failed to apply colormap to image source {id:?}, its {:?} format isn't single-channel",
+                        img.texture_descriptor.format
+                    );
+                    continue;
+                }
+            }
+        }
+
+        // Encoded normals are data and stay linear.
+        let encodable = if settings.channel_mode == ExportChannelMode::NormalEncode {
+            into_dynamic(img.into_owned()).map(|dynamic| (dynamic, false))
+        } else {
+            into_encodable(img, &settings.extension, settings.dithering)
+        };
+        let (mut dynamic, srgb) = match encodable {
+            Ok(encodable) => encodable,
+            Err(e) => {
+                error!("failed to encode frame of image source {id:?}: {e}");
+                continue;
+            }
+        };
+        // The factor the source was actually enlarged by, which may be lower than requested.
+        let supersample = sources
+            .get(id)
+            .map_or(1, |gpu_source| gpu_source.supersample);
+        if supersample > 1 {
+            let resize = ResizeSettings::new(
+                (dynamic.width() / supersample).max(1),
+                (dynamic.height() / supersample).max(1),
+            );
+            if let Some(downsampled) = resize.apply(&dynamic, srgb) {
+                dynamic = downsampled;
+            }
+        }
+        let exposure_scale = settings
+            .auto_exposure
+            .map(|auto_exposure| auto_exposure.apply(&mut dynamic, srgb));
+        if settings.motion_threshold > 0.0 {
+            let thumbnail = motion_thumbnail(&dynamic);
+            if let Some(reference) = &state.motion_reference {
+                let changed = changed_fraction(reference, &thumbnail);
+                if changed <= settings.motion_threshold {
+                    frame_log!(
+                        settings,
+                        "skipping frame of image source {id:?}, only {changed} changed"
+                    );
+                    continue;
+                }
+            }
+            state.motion_reference = Some(thumbnail);
+        }
+
+        let output_size = settings.output_size(dynamic.width(), dynamic.height());
+        let encoded = match encode_frame(settings, dynamic, srgb, viewports, state.frame) {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                error!("failed to encode frame of image source {id:?}: {e}");
+                continue;
+            }
+        };
+        let encode_time = encode_start.elapsed();
+
+        let size = encoded.iter().map(|(_, bytes)| bytes.len() as u64).sum();
+        if settings
+            .max_output_bytes
+            .is_some_and(|max| state.bytes_written + size > max)
+        {
+            info!(
+                "image export to {} reached its output quota after {} bytes",
+                settings.output_dir, state.bytes_written
+            );
+            state.finished = true;
+            sender.send(ExportMessage::Finished {
+                entity,
+                reason: ExportFinishedReason::OutputQuotaExceeded,
+            });
+            continue;
+        }
+
+        let path = frame_path(&root, settings, state);
+        let readback_time = frames.readback_time(id).unwrap_or_default();
+        frame_log!(
+            settings,
+            "saving frame {} of image source {id:?} to {} ({size} bytes, read back in {readback_time:?}, encoded in {encode_time:?})",
+            state.frame,
+            path.display()
+        );
+        if let Err(e) = write_frame(settings, &path, &encoded) {
+            error!("failed to save {}: {e}", path.display());
+            continue;
+        }
+        if settings.verify_writes {
+            if let Err(reason) = verify_frame(settings, &path, &encoded) {
+                error!("verification of {} failed: {reason}", path.display());
+                sender.send(ExportMessage::VerificationFailed {
+                    entity,
+                    frame: state.frame,
+                    path,
+                    reason,
+                });
+                continue;
+            }
+        }
+        #[cfg(feature = "open")]
+        if settings.open_after_write {
+            if let Err(e) = opener::open(&path) {
+                error!("failed to open {}: {e}", path.display());
+            }
+        }
+        if let Some(sidecar) = sidecar.filter(|_| settings.sidecar.is_some()) {
+            let image = SidecarImage {
+                size: output_size,
+                swapped_axes: settings.rotate.swaps_axes(),
+            };
+            if let Err(e) = sidecar.write(&path, state.frame, exposure_scale, image) {
+                error!("failed to save sidecar of {}: {e}", path.display());
+            }
+        }
+        if let Some(signal_file) = &settings.signal_file {
+            let signal_path = root.0.join(signal_file);
+            if let Err(e) = write_signal_file(&signal_path, state.frame, &path) {
+                error!("failed to update {}: {e}", signal_path.display());
+            }
+        }
+
+        if let Some(max_files) = settings.max_files {
+            state.written.push_back(path.clone());
+            while state.written.len() > max_files {
+                let oldest = state.written.pop_front().unwrap();
+                remove_frame(&oldest);
+            }
+        }
+
+        if let Some(timing) = &mut timing {
+            timing.write(
+                frame_count.0,
+                entity,
+                state.frame,
+                Some((readback_time, encode_time)),
+            );
+        }
+        sender.send(ExportMessage::FrameWritten {
+            entity,
+            frame: state.frame,
+            path,
+            bytes: size,
+        });
+        state.frame_written(settings);
+        state.bytes_written += size;
+    }
+
+    for group in advanced_groups {
+        *group_frames.entry(group).or_default() += 1;
+    }
+    if let Some(timing) = &mut timing {
+        timing.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+    use bevy::render::render_resource::TextureDimension;
+
+    use super::*;
+    use crate::events::ExportFinished;
+    use crate::plugin::ImageExportBundle;
+    use crate::session::{update_recording_sessions, RecordingSession};
+
+    #[test]
+    fn encodable_frames_store_srgb() {
+        let linear = Image::new(
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![128, 0, 255, 255],
+            TextureFormat::Rgba8Unorm,
+        );
+        let (dynamic, srgb) =
+            into_encodable(Cow::Borrowed(&linear), "png", Dithering::None).unwrap();
+        assert!(srgb);
+        assert_eq!(dynamic.to_rgba8().get_pixel(0, 0).0, [188, 0, 255, 255]);
+
+        let float =
+            convert_export_image_dithered(&linear, TextureFormat::Rgba32Float, Dithering::None)
+                .unwrap();
+        let (dynamic, srgb) =
+            into_encodable(Cow::Borrowed(&float), "exr", Dithering::None).unwrap();
+        assert!(!srgb);
+        assert!(matches!(dynamic, DynamicImage::ImageRgba32F(_)));
+    }
+
+    #[test]
+    fn buckets_are_named_by_utc_date() {
+        assert_eq!(bucket_name(0), "1970-01-01T00-00");
+        assert_eq!(
+            bucket_name(951_782_400 + 13 * 3600 + 7 * 60 + 59),
+            "2000-02-29T13-07"
+        );
+        assert_eq!(bucket_name(1_700_000_000), "2023-11-14T22-13");
+    }
+
+    #[test]
+    fn frames_are_named_by_number() {
+        let root = DiskExportRoot("root".into());
+        let settings = ImageExportSettings::default();
+        let mut state = DiskExportState {
+            frame: 42,
+            ..default()
+        };
+        assert_eq!(
+            frame_path(&root, &settings, &state),
+            Path::new("root/out/00042.png")
+        );
+
+        state.bucket = Some((0, "1970-01-01T00-00".into()));
+        assert_eq!(
+            frame_path(&root, &settings, &state),
+            Path::new("root/out/1970-01-01T00-00/00042.png")
+        );
+    }
+
+    #[test]
+    fn captures_are_handled_once_written() {
+        let export = ImageExport {
+            requested_captures: 1,
+            ..default()
+        };
+        let settings = ImageExportSettings {
+            mode: CaptureMode::OnRequest,
+            ..default()
+        };
+        let mut states = DiskExportStates::default();
+        let entity = Entity::from_raw(0);
+        states.0.insert(entity, DiskExportState::default());
+        assert!(states.wants_frame(entity, &export, &settings, 0));
+
+        states.0.get_mut(&entity).unwrap().frame_written(&settings);
+        assert!(!states.wants_frame(entity, &export, &settings, 1));
+    }
+
+    #[test]
+    fn paused_sessions_skip_frames_read_back_for_other_sinks() {
+        let mut app = App::new();
+        app.add_event::<ExportFinished>()
+            .add_systems(Update, update_recording_sessions);
+        let mut queue = CommandQueue::default();
+        let session = RecordingSession::start(
+            &mut Commands::new(&mut queue, &app.world),
+            ImageExportBundle::default(),
+        );
+        queue.apply(&mut app.world);
+        session.pause();
+        app.update();
+
+        let entity = session.entity();
+        let export = app.world.get::<ImageExport>(entity).unwrap();
+        let settings = app.world.get::<ImageExportSettings>(entity).unwrap();
+        let states = DiskExportStates::default();
+        assert!(!states.wants_frame(entity, export, settings, 0));
+        // A streaming sink on the entity still reads the frame back, which isn't written.
+        assert!(skips_frame(export, settings, 0));
+
+        session.resume();
+        app.update();
+        let export = app.world.get::<ImageExport>(entity).unwrap();
+        let settings = app.world.get::<ImageExportSettings>(entity).unwrap();
+        assert!(!skips_frame(export, settings, 0));
+    }
+
+    #[test]
+    fn invalid_max_fps_writes_every_frame() {
+        let export = ImageExport::default();
+        let mut states = DiskExportStates::default();
+        let entity = Entity::from_raw(0);
+        for max_fps in [0.0, -24.0, f64::NAN, f64::INFINITY, 1e-300] {
+            let settings = ImageExportSettings {
+                max_fps: Some(max_fps),
+                ..default()
+            };
+            assert_eq!(settings.frame_interval(), None);
+            assert!(states.wants_frame(entity, &export, &settings, 0));
+        }
+
+        let mut settings = ImageExportSettings {
+            max_fps: Some(24.0),
+            ..default()
+        };
+        assert!(!states.wants_frame(entity, &export, &settings, 0));
+        let mut app = App::new();
+        app.init_resource::<DiskExportStates>()
+            .add_systems(Update, pace_disk_exports);
+        let exporter = app.world.spawn(settings.clone()).id();
+        let interval = |app: &App| {
+            app.world.resource::<DiskExportStates>().0[&exporter]
+                .rate_limiter
+                .as_ref()
+                .map(RateLimiter::interval)
+        };
+        app.update();
+        assert_eq!(interval(&app), Some(Duration::from_secs_f64(1.0 / 24.0)));
+
+        settings.max_fps = Some(50.0);
+        app.world.entity_mut(exporter).insert(settings);
+        app.update();
+        assert_eq!(interval(&app), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn formats_replace_other_extensions() {
+        let mut app = App::new();
+        app.add_systems(Update, resolve_export_extensions);
+        let entity = app
+            .world
+            .spawn(ImageExportSettings {
+                format: Some(ImageExportFormat::Jpeg),
+                frame_name: Some(FrameNameFormatter::new(|frame| {
+                    format!("shot.{frame:04}.png")
+                })),
+                ..default()
+            })
+            .id();
+        app.update();
+
+        let settings = app.world.get::<ImageExportSettings>(entity).unwrap();
+        assert_eq!(settings.extension, "jpg");
+        let state = DiskExportState {
+            frame: 7,
+            ..default()
+        };
+        assert_eq!(
+            frame_path(&DiskExportRoot("root".into()), settings, &state),
+            Path::new("root/out/shot.0007.jpg")
+        );
+    }
+
+    #[test]
+    fn clips_and_formatters_name_frames() {
+        let root = DiskExportRoot("root".into());
+        let state = DiskExportState {
+            frame: 42,
+            ..default()
+        };
+        let clips = ImageExportSettings {
+            clip: Some(ClipExport { frames: 10 }),
+            ..default()
+        };
+        assert_eq!(
+            frame_path(&root, &clips, &state),
+            Path::new("root/out/clip_00004/02.png")
+        );
+
+        // The extension of the export replaces one added by the formatter.
+        let formatted = ImageExportSettings {
+            extension: "jpg".into(),
+            frame_name: Some(FrameNameFormatter::new(|frame| {
+                format!("shot010.{frame:04}.png")
+            })),
+            ..default()
+        };
+        assert_eq!(
+            frame_path(&root, &formatted, &state),
+            Path::new("root/out/shot010.0042.jpg")
+        );
+    }
+}
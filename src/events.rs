@@ -0,0 +1,186 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use bevy::{prelude::*, utils::HashMap};
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::destination::write_destination_frame;
+use crate::frame_cache::FrameCache;
+use crate::plugin::ImageExport;
+
+/// Running totals of an image export, updated after every frame written to disk.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ExportStats {
+    pub frames_written: u64,
+    pub bytes_written: u64,
+    /// Frames written per second, averaged over about a second.
+    pub achieved_fps: f32,
+}
+
+/// Sent for every frame written to disk, in the order the files were written.
+#[derive(Event, Clone, Debug)]
+pub struct FrameExported {
+    pub entity: Entity,
+    pub frame: u32,
+    pub path: PathBuf,
+}
+
+/// Sent when an export stops on its own. The [`ImageExport`] component is removed from the
+/// exporting entity at the same time.
+#[derive(Event, Clone, Debug)]
+pub struct ExportFinished {
+    pub entity: Entity,
+    pub reason: ExportFinishedReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFinishedReason {
+    /// Writing the next frame would have exceeded `ImageExportSettings::max_output_bytes`.
+    OutputQuotaExceeded,
+    /// The render frame passed `ImageExportSettings::end_frame`.
+    EndFrameReached,
+    /// [`RecordingSession::stop`](crate::RecordingSession::stop) was called.
+    Stopped,
+}
+
+/// Sent when a frame written to disk doesn't match the encoded frame when read back, see
+/// [`ImageExportSettings::verify_writes`](crate::ImageExportSettings::verify_writes). The
+/// frame number is reused for the next frame.
+#[derive(Event, Clone, Debug)]
+pub struct FrameVerificationFailed {
+    pub entity: Entity,
+    pub frame: u32,
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// Messages sent from the render world back to the main world.
+pub(crate) enum ExportMessage {
+    FrameWritten {
+        entity: Entity,
+        frame: u32,
+        path: PathBuf,
+        bytes: u64,
+    },
+    Finished {
+        entity: Entity,
+        reason: ExportFinishedReason,
+    },
+    VerificationFailed {
+        entity: Entity,
+        frame: u32,
+        path: PathBuf,
+        reason: String,
+    },
+    DestinationFrame {
+        destination: AssetId<Image>,
+        frame: Arc<Image>,
+    },
+    CachedFrame {
+        entity: Entity,
+        time: Duration,
+        frame: Arc<Image>,
+    },
+}
+
+#[derive(Resource, Clone)]
+pub(crate) struct ExportMessageSender(pub Sender<ExportMessage>);
+
+impl ExportMessageSender {
+    pub fn send(&self, message: ExportMessage) {
+        // The receiver lives in the main world and is only dropped together with the app.
+        self.0.send(message).ok();
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct ExportMessageReceiver(pub Receiver<ExportMessage>);
+
+pub(crate) fn export_message_channel() -> (ExportMessageSender, ExportMessageReceiver) {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    (ExportMessageSender(sender), ExportMessageReceiver(receiver))
+}
+
+pub(crate) fn update_achieved_fps(
+    mut stats: Query<(Entity, &mut ExportStats)>,
+    time: Res<Time>,
+    mut frames_written: Local<HashMap<Entity, u64>>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    frames_written.retain(|entity, _| stats.contains(*entity));
+    // Exponential moving average with a time constant of one second.
+    let weight = 1.0 - (-delta).exp();
+    for (entity, mut stats) in &mut stats {
+        let previous = frames_written
+            .insert(entity, stats.frames_written)
+            .unwrap_or(stats.frames_written);
+        let fps = stats.frames_written.saturating_sub(previous) as f32 / delta;
+        stats.achieved_fps += (fps - stats.achieved_fps) * weight;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn receive_export_messages(
+    mut commands: Commands,
+    receiver: Res<ExportMessageReceiver>,
+    mut stats: Query<&mut ExportStats>,
+    mut exported: EventWriter<FrameExported>,
+    mut finished: EventWriter<ExportFinished>,
+    mut verification_failed: EventWriter<FrameVerificationFailed>,
+    mut images: ResMut<Assets<Image>>,
+    mut cache: Option<ResMut<FrameCache>>,
+) {
+    for message in receiver.0.try_iter() {
+        match message {
+            ExportMessage::FrameWritten {
+                entity,
+                frame,
+                path,
+                bytes,
+            } => {
+                if let Ok(mut stats) = stats.get_mut(entity) {
+                    stats.frames_written += 1;
+                    stats.bytes_written += bytes;
+                }
+                exported.send(FrameExported {
+                    entity,
+                    frame,
+                    path,
+                });
+            }
+            ExportMessage::Finished { entity, reason } => {
+                if let Some(mut entity_commands) = commands.get_entity(entity) {
+                    entity_commands.remove::<ImageExport>();
+                }
+                finished.send(ExportFinished { entity, reason });
+            }
+            ExportMessage::VerificationFailed {
+                entity,
+                frame,
+                path,
+                reason,
+            } => {
+                verification_failed.send(FrameVerificationFailed {
+                    entity,
+                    frame,
+                    path,
+                    reason,
+                });
+            }
+            ExportMessage::DestinationFrame { destination, frame } => {
+                write_destination_frame(&mut images, destination, &frame);
+            }
+            ExportMessage::CachedFrame {
+                entity,
+                time,
+                frame,
+            } => {
+                if let Some(cache) = cache.as_mut() {
+                    cache.push(entity, time, frame);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::convert::{linear_to_srgb, srgb_to_linear};
+
+/// Scales every frame so that its mean luminance hits a target, e.g. to normalize the
+/// brightness of dataset frames rendered under very different lighting.
+///
+/// This is a lossy pass on the CPU after the frame is read back: values scaled above 1 are
+/// clipped in 8-bit formats. The applied scale is recorded in the sidecar, if enabled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposure {
+    /// Mean linear luminance every frame is scaled to.
+    pub target_mean: f32,
+    /// Lower bound of the applied scale.
+    pub min_scale: f32,
+    /// Upper bound of the applied scale, so that black frames aren't amplified into noise.
+    pub max_scale: f32,
+    /// Also scales the color channels separately so that their means match (gray world white
+    /// balance).
+    pub white_balance: bool,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            target_mean: 0.18,
+            min_scale: 1.0 / 16.0,
+            max_scale: 16.0,
+            white_balance: false,
+        }
+    }
+}
+
+impl AutoExposure {
+    /// Normalizes `image`, decoding the colors of `srgb` images to linear space first. Returns
+    /// the scale applied to the red, green and blue channels.
+    pub(crate) fn apply(&self, image: &mut DynamicImage, srgb: bool) -> [f32; 3] {
+        let float = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let mut linear = image.to_rgba32f();
+        if srgb {
+            for pixel in linear.pixels_mut() {
+                for c in &mut pixel.0[..3] {
+                    *c = srgb_to_linear(*c);
+                }
+            }
+        }
+
+        let scale = self.scale(&linear);
+        for pixel in linear.pixels_mut() {
+            for (c, scale) in pixel.0[..3].iter_mut().zip(scale) {
+                *c *= scale;
+            }
+        }
+
+        *image = if float {
+            DynamicImage::ImageRgba32F(linear)
+        } else {
+            let encode = |value: f32| {
+                let value = if srgb { linear_to_srgb(value) } else { value };
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            };
+            DynamicImage::ImageRgba8(RgbaImage::from_fn(
+                linear.width(),
+                linear.height(),
+                |x, y| {
+                    let [r, g, b, a] = linear.get_pixel(x, y).0;
+                    Rgba([
+                        encode(r),
+                        encode(g),
+                        encode(b),
+                        (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+                    ])
+                },
+            ))
+        };
+        scale
+    }
+
+    fn scale(&self, linear: &Rgba32FImage) -> [f32; 3] {
+        let pixels = (linear.width() as f64 * linear.height() as f64).max(1.0);
+        let mut sums = [0.0f64; 3];
+        for pixel in linear.pixels() {
+            for (sum, c) in sums.iter_mut().zip(&pixel.0[..3]) {
+                *sum += *c as f64;
+            }
+        }
+        let means = sums.map(|sum| (sum / pixels) as f32);
+        let luminance = 0.2126 * means[0] + 0.7152 * means[1] + 0.0722 * means[2];
+        let clamp = |scale: f32| {
+            if scale.is_finite() {
+                scale.clamp(self.min_scale, self.max_scale)
+            } else {
+                self.max_scale
+            }
+        };
+        if self.white_balance {
+            means.map(|mean| clamp(self.target_mean / mean))
+        } else {
+            [clamp(self.target_mean / luminance); 3]
+        }
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent, render_asset::RenderAssets,
+        render_resource::TextureFormat,
+    },
+    utils::HashSet,
+};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+
+use crate::convert::{encode_yuv420, Yuv420Layout, YuvFormat};
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+/// Writes the raw pixels of every exported frame to a named pipe, e.g. one created with
+/// `mkfifo` that ffmpeg reads from, or encodes them with an ffmpeg process of its own, see
+/// [`with_encoder`](Self::with_encoder).
+///
+/// Frames are written without row padding in the source's texture format, so for an
+/// `Rgba8UnormSrgb` source ffmpeg needs `-f rawvideo -pix_fmt rgba -s [width]x[height]`.
+/// With `yuv` set, they are converted to YUV 4:2:0 first, e.g. for `-pix_fmt nv12`.
+/// The pipe is opened on a separate thread once the first frame is exported, which waits for a
+/// reader to connect. When the reader disconnects, the pipe is reopened for the next frame.
+/// Frames are dropped while the reader falls behind. The export stops if the pipe can't be
+/// opened, e.g. because its directory doesn't exist.
+///
+/// With `segment_duration`, the recording is split into segments, e.g. so that a crash loses at
+/// most the segment being encoded. `{segment}` in the path is replaced with the zero-padded
+/// number of the segment, or appended to the file name, and the next segment is opened with the
+/// first frame captured the duration after the first frame of the current one. With an
+/// encoder, every segment is a playable video finished by its own ffmpeg process. Without one,
+/// the segments hold raw frames: they are pipes read by an ffmpeg each, or else regular files
+/// created for them.
+#[derive(Component, Clone)]
+pub struct FifoExport {
+    pub path: PathBuf,
+    /// Frames buffered for a slow reader before new ones are dropped.
+    pub max_queued_frames: usize,
+    /// Converts frames to YUV 4:2:0 before they are written.
+    pub yuv: Option<YuvFormat>,
+    /// Closes the current segment and opens the next one after this long, see above.
+    pub segment_duration: Option<Duration>,
+    /// Encodes the frames into a video at `path` instead of writing them to it.
+    pub encoder: Option<FfmpegEncoder>,
+    writer: Arc<Mutex<WriterState>>,
+    /// Number of the next segment, kept when the pipe is reopened.
+    next_segment: Arc<AtomicU32>,
+}
+
+/// Encodes the frames of a [`FifoExport`] with an `ffmpeg` process, started for every segment
+/// and finishing its video when the segment ends.
+///
+/// ffmpeg reads 8-bit RGBA, BGRA and single-channel frames, and YUV frames of
+/// [`FifoExport::yuv`]. Frames of other formats stop the export.
+#[derive(Clone, Debug)]
+pub struct FfmpegEncoder {
+    /// The ffmpeg executable, looked up in the `PATH` by default.
+    pub program: PathBuf,
+    /// Frame rate of the video. Every exported frame becomes a frame of the video, so this
+    /// should match the rate frames are exported at.
+    pub frame_rate: f64,
+    /// Output options, placed before the path of the video.
+    pub output_args: Vec<String>,
+}
+
+impl Default for FfmpegEncoder {
+    fn default() -> Self {
+        Self {
+            program: "ffmpeg".into(),
+            frame_rate: 60.0,
+            output_args: ["-vcodec", "libx264", "-crf", "25", "-pix_fmt", "yuv420p"]
+                .map(String::from)
+                .into(),
+        }
+    }
+}
+
+impl FfmpegEncoder {
+    /// Starts an ffmpeg process encoding frames like `frame` into a video at `path`.
+    fn spawn(&self, path: PathBuf, frame: &FifoFrame) -> io::Result<Output> {
+        let Some(pixel_format) = frame.pixel_format() else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "ffmpeg can't read frames of this format, convert them with `yuv`",
+            ));
+        };
+        let size = frame.size();
+        let mut encoder = Command::new(&self.program)
+            .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pix_fmt"])
+            .arg(pixel_format)
+            .arg("-s")
+            .arg(format!("{}x{}", size.x, size.y))
+            .arg("-r")
+            .arg(self.frame_rate.to_string())
+            .args(["-i", "-"])
+            .args(&self.output_args)
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        let stdin = encoder.stdin.take().expect("stdin of ffmpeg is piped");
+        Ok(Output {
+            path,
+            writer: Box::new(stdin),
+            encoder: Some(encoder),
+        })
+    }
+}
+
+#[derive(Default)]
+enum WriterState {
+    /// No frame was exported yet, or the reader disconnected.
+    #[default]
+    Idle,
+    Running(Sender<QueuedFrame>, JoinHandle<WriterExit>),
+    /// The pipe couldn't be opened.
+    Stopped,
+}
+
+/// Why the writer thread of a [`FifoExport`] stopped.
+enum WriterExit {
+    /// The export was dropped or the pipe closed, e.g. because its reader disconnected.
+    Closed,
+    OpenFailed,
+}
+
+/// The pipe or file frames are written to, or the input of an ffmpeg process encoding them.
+struct Output {
+    path: PathBuf,
+    writer: Box<dyn Write + Send>,
+    encoder: Option<Child>,
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let Some(mut encoder) = self.encoder.take() else {
+            return;
+        };
+        // Closing its input lets ffmpeg finish the video.
+        self.writer = Box::new(io::sink());
+        let path = self.path.display();
+        match encoder.wait() {
+            Ok(status) if status.success() => info!("finished encoding {path}"),
+            Ok(status) => error!("ffmpeg failed to encode {path}: {status}"),
+            Err(e) => error!("failed to wait for ffmpeg encoding {path}: {e}"),
+        }
+    }
+}
+
+impl FifoExport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_queued_frames: 4,
+            yuv: None,
+            segment_duration: None,
+            encoder: None,
+            writer: default(),
+            next_segment: default(),
+        }
+    }
+
+    /// Splits the recording into segments of `duration`, see above.
+    pub fn with_segment_duration(mut self, duration: Duration) -> Self {
+        self.segment_duration = Some(duration);
+        self
+    }
+
+    /// Encodes the frames into a video at `path` with `encoder`.
+    pub fn with_encoder(mut self, encoder: FfmpegEncoder) -> Self {
+        self.encoder = Some(encoder);
+        self
+    }
+
+    /// Opens the pipe, or the next segment if the recording is segmented, for frames like
+    /// `frame`.
+    fn open(
+        path: &Path,
+        segment: Option<u32>,
+        encoder: Option<&FfmpegEncoder>,
+        frame: &FifoFrame,
+    ) -> io::Result<Output> {
+        let path = match segment {
+            Some(segment) => segment_path(path, segment),
+            None => path.into(),
+        };
+        if let Some(encoder) = encoder {
+            return encoder.spawn(path, frame);
+        }
+        let file = match segment {
+            Some(_) => OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)?,
+            None => OpenOptions::new().write(true).open(&path)?,
+        };
+        Ok(Output {
+            path,
+            writer: Box::new(file),
+            encoder: None,
+        })
+    }
+
+    /// Spawns the thread writing the queued frames.
+    fn spawn_writer(&self) -> (Sender<QueuedFrame>, JoinHandle<WriterExit>) {
+        let (sender, receiver) = bounded::<QueuedFrame>(self.max_queued_frames);
+        let path = self.path.clone();
+        let segment_duration = self.segment_duration;
+        let encoder = self.encoder.clone();
+        let next_segment = self.next_segment.clone();
+        let writer = std::thread::spawn(move || {
+            let open = |frame: &FifoFrame| {
+                let segment =
+                    segment_duration.map(|_| next_segment.fetch_add(1, Ordering::Relaxed));
+                match FifoExport::open(&path, segment, encoder.as_ref(), frame) {
+                    Ok(output) => {
+                        info!("started writing frames to {}", output.path.display());
+                        Some(output)
+                    }
+                    Err(e) => {
+                        error!("failed to open {}: {e}", path.display());
+                        None
+                    }
+                }
+            };
+            let mut output: Option<Output> = None;
+            let mut segment_start = None;
+            for (time, frame) in receiver {
+                let start = *segment_start.get_or_insert(time);
+                if segment_duration.is_some_and(|duration| time.duration_since(start) >= duration) {
+                    // Dropping the segment closes it, which ends the stream of its reader.
+                    output = None;
+                    segment_start = Some(time);
+                }
+                let current = match &mut output {
+                    Some(current) => current,
+                    None => match open(&frame) {
+                        Some(next) => output.insert(next),
+                        None => return WriterExit::OpenFailed,
+                    },
+                };
+                if let Err(e) = current.writer.write_all(frame.data()) {
+                    match e.kind() {
+                        ErrorKind::BrokenPipe => {
+                            warn!("reader of {} disconnected", current.path.display())
+                        }
+                        _ => error!("failed to write to {}: {e}", current.path.display()),
+                    }
+                    return WriterExit::Closed;
+                }
+            }
+            WriterExit::Closed
+        });
+        (sender, writer)
+    }
+}
+
+/// `path` with `{segment}` replaced by the zero-padded `segment`, or with the segment appended to
+/// the file name, e.g. `frames_00002.yuv`.
+fn segment_path(path: &Path, segment: u32) -> PathBuf {
+    let segment = format!("{segment:05}");
+    let path = path.to_string_lossy();
+    if path.contains("{segment}") {
+        return path.replace("{segment}", &segment).into();
+    }
+    let path = Path::new(path.as_ref());
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("_{segment}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// A frame along with the time it was captured, which segments are rotated by.
+type QueuedFrame = (Instant, FifoFrame);
+
+/// A frame queued for the writer thread. Raw frames are shared with the other sinks instead of
+/// being copied.
+enum FifoFrame {
+    Raw(Arc<Image>),
+    Yuv {
+        data: Vec<u8>,
+        size: UVec2,
+        layout: Yuv420Layout,
+    },
+}
+
+impl FifoFrame {
+    fn data(&self) -> &[u8] {
+        match self {
+            FifoFrame::Raw(image) => &image.data,
+            FifoFrame::Yuv { data, .. } => data,
+        }
+    }
+
+    fn size(&self) -> UVec2 {
+        match self {
+            FifoFrame::Raw(image) => image.size(),
+            FifoFrame::Yuv { size, .. } => *size,
+        }
+    }
+
+    /// The `-pix_fmt` ffmpeg reads the frame with, if it supports the format.
+    fn pixel_format(&self) -> Option<&'static str> {
+        match self {
+            FifoFrame::Raw(image) => match image.texture_descriptor.format {
+                TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Some("rgba"),
+                TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Some("bgra"),
+                TextureFormat::R8Unorm => Some("gray"),
+                _ => None,
+            },
+            FifoFrame::Yuv { layout, .. } => Some(match layout {
+                Yuv420Layout::Nv12 => "nv12",
+                Yuv420Layout::I420 => "yuv420p",
+            }),
+        }
+    }
+}
+
+impl ExtractComponent for FifoExport {
+    type Query = (&'static FifoExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (FifoExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some((this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct FifoExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: FifoExport,
+}
+
+pub(crate) fn write_to_fifos(
+    fifos: Query<(Entity, &FifoExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (entity, fifo, source_handle) in &fifos {
+        let id = source_handle.id();
+        if !source_is_present(id, &sources, &images, &mut missing_sources) {
+            continue;
+        }
+        let mut writer = fifo.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(*writer, WriterState::Stopped) {
+            continue;
+        }
+        let Some(frame) = frames.get_export_shared(entity, id) else {
+            continue;
+        };
+        let frame = match fifo.yuv {
+            Some(yuv) => match encode_yuv420(&frame, yuv) {
+                Some(data) => FifoFrame::Yuv {
+                    data,
+                    size: frame.size(),
+                    layout: yuv.layout,
+                },
+                None => {
+                    error!(
+                        "cannot convert frame of image source {id:?} with format {:?} to YUV",
+                        frame.texture_descriptor.format
+                    );
+                    continue;
+                }
+            },
+            None => FifoFrame::Raw(frame),
+        };
+
+        if matches!(*writer, WriterState::Idle) {
+            let (sender, thread) = fifo.spawn_writer();
+            *writer = WriterState::Running(sender, thread);
+        }
+        let WriterState::Running(sender, _) = &*writer else {
+            continue;
+        };
+        match sender.try_send((Instant::now(), frame)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!(
+                    "dropping frame of image source {id:?}, {} is full",
+                    fifo.path.display()
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                let WriterState::Running(_, thread) = std::mem::take(&mut *writer) else {
+                    continue;
+                };
+                // The thread is exiting, it dropped its end of the queue.
+                if !matches!(thread.join(), Ok(WriterExit::Closed)) {
+                    error!(
+                        "stopped writing frames of image source {id:?} to {}",
+                        fifo.path.display()
+                    );
+                    *writer = WriterState::Stopped;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_are_numbered_sequentially() {
+        assert_eq!(
+            segment_path(Path::new("out/frames_{segment}.yuv"), 2),
+            Path::new("out/frames_00002.yuv")
+        );
+        assert_eq!(
+            segment_path(Path::new("out/frames.yuv"), 13),
+            Path::new("out/frames_00013.yuv")
+        );
+        assert_eq!(
+            segment_path(Path::new("frames"), 0),
+            Path::new("frames_00000")
+        );
+    }
+
+    fn yuv_frame(data: Vec<u8>) -> FifoFrame {
+        FifoFrame::Yuv {
+            data,
+            size: UVec2::ONE,
+            layout: Yuv420Layout::Nv12,
+        }
+    }
+
+    #[test]
+    fn stops_when_the_pipe_cannot_be_opened() {
+        let dir = std::env::temp_dir().join(format!("fifo_missing_{}", std::process::id()));
+        let missing_encoder = FfmpegEncoder {
+            program: dir.join("ffmpeg"),
+            ..default()
+        };
+        for fifo in [
+            FifoExport::new(dir.join("frames.raw")),
+            FifoExport::new("frames.mp4").with_encoder(missing_encoder),
+        ] {
+            let (sender, writer) = fifo.spawn_writer();
+            sender.send((Instant::now(), yuv_frame(vec![0]))).unwrap();
+            assert!(matches!(writer.join().unwrap(), WriterExit::OpenFailed));
+        }
+    }
+
+    /// Removes the directory when the test ends, even if it fails.
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn rotates_segments_after_their_duration() {
+        let dir =
+            TempDir(std::env::temp_dir().join(format!("fifo_segments_{}", std::process::id())));
+        std::fs::create_dir_all(&dir.0).unwrap();
+        let fifo = FifoExport::new(dir.0.join("frames_{segment}.raw"))
+            .with_segment_duration(Duration::from_millis(50));
+        let (sender, writer) = fifo.spawn_writer();
+        let start = Instant::now();
+        for (i, millis) in [0, 30, 60, 140].into_iter().enumerate() {
+            let time = start + Duration::from_millis(millis);
+            sender.send((time, yuv_frame(vec![i as u8]))).unwrap();
+        }
+        drop(sender);
+        writer.join().unwrap();
+
+        for (segment, frames) in [vec![0, 1], vec![2], vec![3]].into_iter().enumerate() {
+            let path = dir.0.join(format!("frames_{segment:05}.raw"));
+            assert_eq!(std::fs::read(path).unwrap(), frames);
+        }
+    }
+}
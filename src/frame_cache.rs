@@ -0,0 +1,191 @@
+use std::{borrow::Cow, collections::VecDeque, sync::Arc, time::Duration};
+
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_asset::RenderAssets, Extract},
+    utils::HashSet,
+};
+use image::imageops::FilterType;
+
+use crate::convert::Dithering;
+use crate::disk::into_encodable;
+use crate::events::{ExportMessage, ExportMessageSender};
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+/// Keeps the frames of exports with [`FrameCacheExport`] in memory, to look up what the export
+/// looked like at a recent point in time, e.g. in interactive debugging tools.
+///
+/// Insert it with [`FrameCache::new`]; without the resource nothing is cached. Frames are
+/// timestamped with the [`Time::elapsed`] of the main world frame they were rendered in.
+#[derive(Resource, Clone, Debug)]
+pub struct FrameCache {
+    capacity: usize,
+    max_size: UVec2,
+    frames: VecDeque<(Entity, Duration, Arc<Image>)>,
+}
+
+impl FrameCache {
+    /// Keeps the last `capacity` frames, downscaled to fit into `max_size`.
+    pub fn new(capacity: usize, max_size: UVec2) -> Self {
+        Self {
+            capacity,
+            max_size: max_size.max(UVec2::ONE),
+            frames: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The cached frame closest to `time`, from any export.
+    pub fn frame_at(&self, time: Duration) -> Option<Image> {
+        self.nearest(self.frames.iter(), time)
+    }
+
+    /// The cached frame of `entity` closest to `time`.
+    pub fn entity_frame_at(&self, entity: Entity, time: Duration) -> Option<Image> {
+        self.nearest(self.frames.iter().filter(|(e, ..)| *e == entity), time)
+    }
+
+    /// Timestamps of the cached frames of any export, oldest first.
+    pub fn timestamps(&self) -> impl Iterator<Item = (Entity, Duration)> + '_ {
+        self.frames.iter().map(|(entity, time, _)| (*entity, *time))
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    fn nearest<'a>(
+        &self,
+        frames: impl Iterator<Item = &'a (Entity, Duration, Arc<Image>)>,
+        time: Duration,
+    ) -> Option<Image> {
+        frames
+            .min_by_key(|(_, frame_time, _)| frame_time.abs_diff(time))
+            .map(|(.., image)| Image::clone(image))
+    }
+
+    /// Adds a frame that was already downscaled by [`fit_frame`].
+    pub(crate) fn push(&mut self, entity: Entity, time: Duration, image: Arc<Image>) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((entity, time, image));
+    }
+}
+
+/// Caches every frame read back from the source in the [`FrameCache`].
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct FrameCacheExport;
+
+impl ExtractComponent for FrameCacheExport {
+    type Query = (
+        &'static FrameCacheExport,
+        &'static Handle<ImageExportSource>,
+    );
+    type Filter = ();
+    type Out = (FrameCacheExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some((*this, source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct FrameCacheExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: FrameCacheExport,
+}
+
+/// Main world time of the extracted frame along with the [`FrameCache`]'s maximum frame size,
+/// or `None` without a cache.
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedFrameCache(Option<(Duration, UVec2)>);
+
+pub(crate) fn extract_frame_cache(
+    cache: Extract<Option<Res<FrameCache>>>,
+    time: Extract<Res<Time>>,
+    mut extracted: ResMut<ExtractedFrameCache>,
+) {
+    extracted.0 = cache.as_ref().map(|cache| (time.elapsed(), cache.max_size));
+}
+
+/// Downscales `image` to fit into `max_size`, like an exported PNG so that linear and float
+/// colors end up as sRGB. Frames that already fit are shared as they are.
+fn fit_frame(image: Arc<Image>, max_size: UVec2) -> Result<Arc<Image>, String> {
+    if !image.size().cmpgt(max_size).any() {
+        return Ok(image);
+    }
+    let (dynamic, srgb) = into_encodable(Cow::Borrowed(&image), "png", Dithering::None)?;
+    Ok(Arc::new(Image::from_dynamic(
+        dynamic.resize(max_size.x, max_size.y, FilterType::Triangle),
+        srgb,
+    )))
+}
+
+pub(crate) fn send_cached_frames(
+    exports: Query<(Entity, &Handle<ImageExportSource>), With<FrameCacheExport>>,
+    cache: Res<ExtractedFrameCache>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    sender: Res<ExportMessageSender>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    let Some((time, max_size)) = cache.0 else {
+        return;
+    };
+    let sources = sources.into_inner();
+    let images = images.into_inner();
+
+    for (entity, source_handle) in &exports {
+        if !source_is_present(source_handle.id(), sources, images, &mut missing_sources) {
+            continue;
+        }
+        let Some(frame) = frames.get_export_shared(entity, source_handle.id()) else {
+            continue;
+        };
+        // Downscaled here so that only the small frame is sent to the main world.
+        match fit_frame(frame, max_size) {
+            Ok(frame) => sender.send(ExportMessage::CachedFrame {
+                entity,
+                time,
+                frame,
+            }),
+            Err(e) => warn!("failed to downscale frame of {entity:?} for the frame cache: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    use super::*;
+
+    fn frame(width: u32, height: u32) -> Arc<Image> {
+        Arc::new(Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        ))
+    }
+
+    #[test]
+    fn fit_frame_shares_frames_that_fit() {
+        let small = frame(16, 8);
+        let fitted = fit_frame(small.clone(), UVec2::new(16, 16)).unwrap();
+        assert!(Arc::ptr_eq(&small, &fitted));
+
+        let fitted = fit_frame(frame(64, 32), UVec2::new(16, 16)).unwrap();
+        assert_eq!(fitted.size(), UVec2::new(16, 8));
+        assert_eq!(&fitted.data[..4], &[255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,52 @@
+use bevy::{
+    prelude::*,
+    render::{camera::RenderTarget, view::RenderLayers},
+    utils::HashSet,
+};
+
+use crate::plugin::ImageExportSource;
+
+/// Limits the export to entities on these render layers, e.g. only a "broadcast" layer in an
+/// NDI feed while a watermark on another layer stays on screen.
+///
+/// Every camera rendering into the export's source gets these `RenderLayers`, also cameras
+/// spawned later. Cameras sharing a target draw into the same texture, so content seen by a
+/// camera rendering to the window only stays out of the export if that camera has its own
+/// target.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ExportRenderLayers(pub RenderLayers);
+
+pub(crate) fn apply_export_render_layers(
+    mut commands: Commands,
+    exports: Query<(&Handle<ImageExportSource>, &ExportRenderLayers)>,
+    sources: Res<Assets<ImageExportSource>>,
+    mut cameras: Query<(Entity, &Camera, Option<&mut RenderLayers>)>,
+    mut applied: Local<HashSet<Entity>>,
+) {
+    for (source_handle, layers) in &exports {
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        for (entity, camera, camera_layers) in &mut cameras {
+            if !matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+            {
+                continue;
+            }
+            match camera_layers {
+                Some(camera_layers) if *camera_layers == layers.0 => continue,
+                Some(mut camera_layers) => *camera_layers = layers.0,
+                None => {
+                    commands.entity(entity).insert(layers.0);
+                }
+            }
+            if applied.insert(entity) {
+                debug!(
+                    "limiting camera {entity:?} to the render layers {:?} of its export",
+                    layers.0
+                );
+            } else {
+                warn!("the render layers of camera {entity:?} were changed, resetting them to those of its export");
+            }
+        }
+    }
+}
@@ -1,10 +1,99 @@
+mod adaptive;
+#[cfg(feature = "burn_in")]
+mod burn_in;
+mod camera;
+mod capture;
+mod channel;
+mod control;
+mod convert;
+#[cfg(feature = "dds")]
+mod dds;
+mod depth;
+mod destination;
+mod disk;
+mod events;
+mod exposure;
+mod fifo;
+mod frame_cache;
+mod layers;
+mod matte;
+mod mip_chain;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod motion;
 mod ndi;
 mod node;
+#[cfg(feature = "png")]
+mod parallel_png;
 mod plugin;
+mod rate;
+mod resize;
+mod session;
+mod sidecar;
+mod stereo;
+mod supersample;
+mod switcher;
+mod timing;
+mod transparency;
+#[cfg(feature = "upload")]
+mod upload;
+mod viewports;
+#[cfg(feature = "websocket")]
+mod websocket;
 
-pub use ndi::{NDIExport, NDIExportBundle, NDIExportPlugin};
-
+pub use adaptive::AdaptiveQuality;
+#[cfg(feature = "burn_in")]
+pub use burn_in::{BurnInPosition, BurnInSettings};
+pub use camera::ExportThisCamera;
+pub use capture::{
+    capture_frame, capture_on_key, wait_for_first_capture, CaptureMode, CaptureRequest,
+    FirstCaptureError, ResetCapture,
+};
+pub use channel::{ChannelExport, ChannelExportBundle, ChannelFrame};
+pub use control::GlobalExportControl;
+pub use convert::{
+    apply_colormap, convert_export_image, convert_export_image_dithered, encode_normals,
+    encode_yuv420, extract_export_channel, linearize_depth, Colormap, Dithering, ExportChannelMode,
+    Yuv420Layout, YuvFormat, YuvMatrix,
+};
+#[cfg(feature = "dds")]
+pub use dds::BcFormat;
+pub use destination::{
+    ImageExportDestination, ImageExportDestinationBundle, ImageExportGpuDestination,
+    ImageExportGpuDestinationBundle,
+};
+pub use disk::{
+    ClipExport, FirstFramePolicy, FrameNameFormatter, ImageExportFormat, ImageExportSettings,
+    Rotation,
+};
+pub use events::{
+    ExportFinished, ExportFinishedReason, ExportStats, FrameExported, FrameVerificationFailed,
+};
+pub use exposure::AutoExposure;
+pub use fifo::{FfmpegEncoder, FifoExport, FifoExportBundle};
+pub use frame_cache::{FrameCache, FrameCacheExport, FrameCacheExportBundle};
+pub use layers::ExportRenderLayers;
+pub use matte::AlphaMatte;
+pub use mip_chain::{MipChainExport, MipChainExportBundle};
+#[cfg(feature = "mmap")]
+pub use mmap::{MmapExport, MmapExportBundle, MmapFrameReader};
+pub use ndi::{
+    NDIColorFormat, NDIConfig, NDIExport, NDIExportBundle, NDIExportPlugin, NDIFrameFormat,
+    NDIRuntime, NDIStatus,
+};
+pub use node::{add_image_export_node, add_image_export_node_named, CAMERA_NODE_NAME, NODE_NAME};
 pub use plugin::{
-    GpuImageExportSource, ImageExportBundle, ImageExportPlugin, ImageExportSource,
-    ImageExportSystems,
+    ExportFormatError, ExportedFrames, GpuImageExportSource, ImageExport, ImageExportBundle,
+    ImageExportPlugin, ImageExportRenderSystems, ImageExportSource, ImageExportSystems,
+    READBACK_BUFFER_USAGES,
 };
+pub use resize::{ResizeFilter, ResizeSettings};
+pub use session::RecordingSession;
+pub use sidecar::SidecarSettings;
+pub use stereo::{StereoExport, StereoLayout};
+pub use switcher::ExportSourceSwitcher;
+pub use transparency::{ExportClearColor, TransparentExport};
+#[cfg(feature = "upload")]
+pub use upload::{UploadExport, UploadExportBundle, UploadMethod};
+#[cfg(feature = "websocket")]
+pub use websocket::{WebSocketExport, WebSocketExportBundle};
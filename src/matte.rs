@@ -0,0 +1,81 @@
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_resource::TextureFormat},
+    utils::HashSet,
+};
+
+use crate::convert::{extract_export_channel, with_data};
+use crate::plugin::{ExportedFrames, ImageExportSource};
+
+/// Replaces the alpha channel of an export's frames with the red channel of another source,
+/// e.g. a white-on-black matte rendered separately from the beauty pass.
+///
+/// Both sources are read back together and frames are only exported once both are available.
+/// The matte must have the same size as the color source, which must have an 8-bit RGBA or
+/// BGRA format, or else the frames are dropped. Only the sinks on the entity that read frames
+/// back export the matted frame, other exports of the color source are unaffected.
+#[derive(Component, Clone, Debug)]
+pub struct AlphaMatte(pub Handle<ImageExportSource>);
+
+impl ExtractComponent for AlphaMatte {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(item.clone())
+    }
+}
+
+pub(crate) fn apply_alpha_mattes(
+    exports: Query<(Entity, &Handle<ImageExportSource>, &AlphaMatte)>,
+    mut frames: ResMut<ExportedFrames>,
+    mut warned: Local<HashSet<Entity>>,
+) {
+    for (entity, source_handle, matte) in &exports {
+        let id = source_handle.id();
+        // Mattes apply on top of a stereo frame composed for the export.
+        let Some(color) = frames.get_export_unpadded(entity, id) else {
+            continue;
+        };
+        let Some(alpha) = frames
+            .get_unpadded(matte.0.id())
+            .and_then(|matte| extract_export_channel(&matte, 0))
+        else {
+            debug!("dropping frame of export {entity:?}, its alpha matte isn't ready");
+            frames.compose(entity, None);
+            continue;
+        };
+        let (width, height) = (color.width() as usize, color.height() as usize);
+        let format = color.texture_descriptor.format;
+        if !matches!(
+            format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        ) {
+            if warned.insert(entity) {
+                warn!("dropping frames of export {entity:?}, cannot apply an alpha matte to image source {id:?} with format {format:?}");
+            }
+            frames.compose(entity, None);
+            continue;
+        }
+        if alpha.width() as usize != width || alpha.height() as usize != height {
+            warn!(
+                "dropping frame of export {entity:?}, its alpha matte is {}x{} instead of {width}x{height}",
+                alpha.width(),
+                alpha.height()
+            );
+            frames.compose(entity, None);
+            continue;
+        }
+        let mut data = color.data.clone();
+        for (pixel, alpha) in data.chunks_exact_mut(4).zip(&alpha.data) {
+            pixel[3] = *alpha;
+        }
+        let image = with_data(&color, format, data);
+        frames.compose(entity, Some(image));
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        render_asset::RenderAssets,
+        render_resource::{
+            BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+            ImageCopyTexture, ImageDataLayout, MapMode, Origin3d, Texture, TextureAspect,
+            TextureFormat,
+        },
+        renderer::{RenderDevice, RenderQueue},
+    },
+    utils::HashSet,
+};
+use futures::channel::oneshot;
+use wgpu::Maintain;
+
+use crate::plugin::{
+    readback_layout, source_is_present, unpad, AdapterDiagnostics, ExportedFrames,
+    ImageExportSource, StreamingSink,
+};
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+/// Size of the identifier, header and section index preceding the level index.
+const KTX2_HEADER_SIZE: usize = 80;
+
+/// Writes all mip levels of the export's source image to a single KTX2 file, e.g. to bake a
+/// texture along with the mips a custom pass rendered into it. The file can be loaded with
+/// Bevy's `ktx2` feature.
+///
+/// The chain is written once, on the first frame the source is exported; insert a new
+/// `MipChainExport` to write it again. Supports 8-bit RGBA/BGRA (linear or sRGB),
+/// `Rgba16Float`, `Rgba32Float`, `R8Unorm` and `R32Float` sources that aren't multisampled.
+#[derive(Component, Clone)]
+pub struct MipChainExport {
+    pub path: PathBuf,
+    written: Arc<AtomicBool>,
+}
+
+impl MipChainExport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            written: default(),
+        }
+    }
+
+    /// Whether the chain was read back, it's written to disk on a separate thread afterwards.
+    pub fn is_written(&self) -> bool {
+        self.written.load(Ordering::Acquire)
+    }
+}
+
+impl ExtractComponent for MipChainExport {
+    type Query = (&'static MipChainExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (MipChainExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some((this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct MipChainExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: MipChainExport,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_mip_chains(
+    exports: Query<(&MipChainExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    diagnostics: Res<AdapterDiagnostics>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (export, source_handle) in &exports {
+        let id = source_handle.id();
+        if export.is_written() || !source_is_present(id, &sources, &images, &mut missing_sources) {
+            continue;
+        }
+        // The source is complete once it was copied for this frame.
+        if frames.get(id).is_none() {
+            continue;
+        }
+        let Some(gpu_image) = sources
+            .get(id)
+            .and_then(|gpu_source| images.get(&gpu_source.source_handle))
+        else {
+            continue;
+        };
+        export.written.store(true, Ordering::Release);
+
+        let texture = &gpu_image.texture;
+        if texture.sample_count() > 1 {
+            error!("cannot export the mip chain of multisampled image source {id:?}");
+            continue;
+        }
+        let levels = match read_back_mip_levels(texture, &render_device, &render_queue) {
+            Ok(levels) => levels,
+            Err(e) => {
+                error!("failed to read back the mip chain of image source {id:?}: {e}");
+                diagnostics.log_once();
+                continue;
+            }
+        };
+        let (format, size, path) = (texture.format(), texture.size(), export.path.clone());
+        std::thread::spawn(move || {
+            let result = encode_ktx2(format, size, &levels)
+                .and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => info!(
+                    "wrote {} mip levels of image source {id:?} to {}",
+                    levels.len(),
+                    path.display()
+                ),
+                Err(e) => error!("failed to write mip chain {}: {e}", path.display()),
+            }
+        });
+    }
+}
+
+/// Copies every mip level of `texture` into one buffer and waits for it, returning the
+/// unpadded data of each level.
+fn read_back_mip_levels(
+    texture: &Texture,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) -> Result<Vec<Vec<u8>>, String> {
+    let format = texture.format();
+    let max_buffer_size = render_device.limits().max_buffer_size;
+    let mut layouts = Vec::new();
+    let mut buffer_size = 0;
+    for level in 0..texture.mip_level_count() {
+        let size = texture.size().mip_level_size(level, texture.dimension());
+        let (bytes_per_row, padded_bytes_per_row, level_size) =
+            readback_layout(size, format, max_buffer_size)?;
+        layouts.push((
+            size,
+            buffer_size,
+            bytes_per_row,
+            padded_bytes_per_row,
+            level_size,
+        ));
+        buffer_size += level_size;
+    }
+    if buffer_size > max_buffer_size {
+        return Err(format!(
+            "the mip chain needs {buffer_size} bytes, exceeding the device limit of {max_buffer_size} bytes"
+        ));
+    }
+
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("image_export_mip_chain"),
+        size: buffer_size,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("image_export_mip_chain"),
+    });
+    for (level, &(size, offset, _, padded_bytes_per_row, _)) in layouts.iter().enumerate() {
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: level as u32,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+    }
+    render_queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (mapping_tx, mut mapping_rx) = oneshot::channel();
+    render_device.map_buffer(&slice, MapMode::Read, move |res| {
+        mapping_tx.send(res).ok();
+    });
+    render_device.poll(Maintain::Wait);
+    match mapping_rx.try_recv() {
+        Ok(Some(Ok(()))) => {}
+        Ok(Some(Err(e))) => return Err(e.to_string()),
+        _ => return Err("mapping didn't finish".into()),
+    }
+
+    let mapped = slice.get_mapped_range();
+    let levels = layouts
+        .iter()
+        .map(
+            |&(_, offset, bytes_per_row, padded_bytes_per_row, level_size)| {
+                let start = offset as usize;
+                unpad(
+                    &mapped[start..start + level_size as usize],
+                    bytes_per_row,
+                    padded_bytes_per_row,
+                )
+            },
+        )
+        .collect();
+    drop(mapped);
+    buffer.unmap();
+    Ok(levels)
+}
+
+/// Channel of a sample in the data format descriptor, in the RGBSDA color model.
+#[derive(Clone, Copy)]
+enum Channel {
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+    Alpha = 15,
+}
+
+/// The Vulkan format, the size of a channel and the channels of a supported format, in memory
+/// order.
+fn ktx2_format(format: TextureFormat) -> Option<(u32, u32, &'static [Channel])> {
+    use Channel::*;
+    const RGBA: &[Channel] = &[Red, Green, Blue, Alpha];
+    const BGRA: &[Channel] = &[Blue, Green, Red, Alpha];
+    Some(match format {
+        TextureFormat::Rgba8Unorm => (37, 1, RGBA),
+        TextureFormat::Rgba8UnormSrgb => (43, 1, RGBA),
+        TextureFormat::Bgra8Unorm => (44, 1, BGRA),
+        TextureFormat::Bgra8UnormSrgb => (50, 1, BGRA),
+        TextureFormat::Rgba16Float => (97, 2, RGBA),
+        TextureFormat::Rgba32Float => (109, 4, RGBA),
+        TextureFormat::R8Unorm => (9, 1, &[Red]),
+        TextureFormat::R32Float => (100, 4, &[Red]),
+        _ => return None,
+    })
+}
+
+/// Builds the basic data format descriptor of an uncompressed format.
+fn data_format_descriptor(format: TextureFormat, type_size: u32, channels: &[Channel]) -> Vec<u8> {
+    let is_float = matches!(
+        format,
+        TextureFormat::Rgba16Float | TextureFormat::Rgba32Float | TextureFormat::R32Float
+    );
+    let block_size = (4 + 24 + 16 * channels.len()) as u32;
+    let mut dfd = Vec::with_capacity(block_size as usize);
+    dfd.extend(block_size.to_le_bytes());
+    // Khronos vendor, basic descriptor type, version 2.
+    dfd.extend(0u32.to_le_bytes());
+    dfd.extend(2u16.to_le_bytes());
+    dfd.extend(((block_size - 4) as u16).to_le_bytes());
+    // RGBSDA color model, BT.709 primaries, linear or sRGB transfer, straight alpha.
+    let transfer = if format.is_srgb() { 2 } else { 1 };
+    dfd.extend([1, 1, transfer, 0]);
+    dfd.extend([0; 4]);
+    dfd.push((type_size * channels.len() as u32) as u8);
+    dfd.extend([0; 7]);
+    for (i, &channel) in channels.iter().enumerate() {
+        let bits = type_size * 8;
+        dfd.extend(((i as u32 * bits) as u16).to_le_bytes());
+        dfd.push((bits - 1) as u8);
+        let mut channel_type = channel as u8;
+        if is_float {
+            // Signed float.
+            channel_type |= 0xc0;
+        } else if format.is_srgb() && matches!(channel, Channel::Alpha) {
+            channel_type |= 0x10;
+        }
+        dfd.push(channel_type);
+        dfd.extend([0; 4]);
+        let (lower, upper) = if is_float {
+            ((-1.0f32).to_bits(), 1.0f32.to_bits())
+        } else {
+            (0, (1u32 << bits) - 1)
+        };
+        dfd.extend(lower.to_le_bytes());
+        dfd.extend(upper.to_le_bytes());
+    }
+    dfd
+}
+
+/// Assembles a KTX2 file holding the mip `levels` of a 2D texture of `size`, largest first.
+fn encode_ktx2(
+    format: TextureFormat,
+    size: Extent3d,
+    levels: &[Vec<u8>],
+) -> Result<Vec<u8>, String> {
+    let (vk_format, type_size, channels) =
+        ktx2_format(format).ok_or_else(|| format!("{format:?} can't be stored as KTX2"))?;
+    let dfd = data_format_descriptor(format, type_size, channels);
+    let dfd_offset = KTX2_HEADER_SIZE + 24 * levels.len();
+    // Levels are aligned to the least common multiple of the texel size and 4, which is the
+    // larger of both for the supported formats.
+    let alignment = (type_size as usize * channels.len()).max(4);
+
+    // The smallest level is stored first.
+    let mut data = Vec::new();
+    let mut level_index = vec![(0, 0); levels.len()];
+    let mut offset = dfd_offset + dfd.len();
+    for (level, bytes) in levels.iter().enumerate().rev() {
+        let padding = offset.next_multiple_of(alignment) - offset;
+        data.resize(data.len() + padding, 0);
+        offset += padding;
+        level_index[level] = (offset as u64, bytes.len() as u64);
+        data.extend_from_slice(bytes);
+        offset += bytes.len();
+    }
+
+    let mut ktx2 = Vec::with_capacity(offset);
+    ktx2.extend(KTX2_IDENTIFIER);
+    for value in [
+        vk_format,
+        type_size,
+        size.width,
+        size.height,
+        // A 2D texture without array layers, with one face and no supercompression.
+        0,
+        0,
+        1,
+        levels.len() as u32,
+        0,
+        dfd_offset as u32,
+        dfd.len() as u32,
+        // No key/value data.
+        0,
+        0,
+    ] {
+        ktx2.extend(value.to_le_bytes());
+    }
+    // No supercompression global data.
+    ktx2.extend([0; 16]);
+    for (offset, len) in level_index {
+        ktx2.extend(offset.to_le_bytes());
+        ktx2.extend(len.to_le_bytes());
+        ktx2.extend(len.to_le_bytes());
+    }
+    ktx2.extend(dfd);
+    ktx2.extend(data);
+    Ok(ktx2)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType};
+
+    use super::*;
+
+    #[test]
+    fn ktx2_files_load_with_every_level() {
+        let size = Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        let levels: Vec<Vec<u8>> = [16, 4, 1]
+            .into_iter()
+            .enumerate()
+            .map(|(level, texels)| (0..texels * 4).map(|i| (level * 64 + i) as u8).collect())
+            .collect();
+        let ktx2 = encode_ktx2(TextureFormat::Rgba8Unorm, size, &levels).unwrap();
+
+        let image = Image::from_buffer(
+            &ktx2,
+            ImageType::Extension("ktx2"),
+            CompressedImageFormats::NONE,
+            false,
+            ImageSampler::Default,
+        )
+        .unwrap();
+        assert_eq!(image.texture_descriptor.format, TextureFormat::Rgba8Unorm);
+        assert_eq!(image.texture_descriptor.size, size);
+        assert_eq!(image.texture_descriptor.mip_level_count, 3);
+        // Levels are loaded largest first.
+        assert_eq!(image.data, levels.concat());
+    }
+}
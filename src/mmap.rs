@@ -0,0 +1,412 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    core::FrameCount,
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::ExtractComponent,
+        render_asset::RenderAssets,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    utils::HashSet,
+};
+use crossbeam_channel::{bounded, Sender, TrySendError};
+use memmap2::Mmap;
+
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+const MAGIC: &[u8; 8] = b"BIEXFRM1";
+const HEADER_SIZE: u64 = 24;
+const ENTRY_SIZE: u64 = 32;
+
+/// Formats that can be stored, by their code in the index.
+const FORMATS: [TextureFormat; 8] = [
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rgba8UnormSrgb,
+    TextureFormat::Bgra8Unorm,
+    TextureFormat::Bgra8UnormSrgb,
+    TextureFormat::Rgba16Float,
+    TextureFormat::Rgba32Float,
+    TextureFormat::R8Unorm,
+    TextureFormat::R32Float,
+];
+
+/// Appends the raw pixels of every exported frame to a single file, instead of writing a file
+/// per frame, e.g. for sequences of many thousands of frames. Read the frames back with
+/// [`MmapFrameReader`].
+///
+/// The file starts with an index of `max_frames` entries holding the offset, size, format and
+/// render frame of every frame, followed by the unpadded pixels of the frames. The file is
+/// replaced once the first frame is exported, and frames beyond `max_frames` are dropped.
+/// Frames are written on a separate thread and dropped while it falls behind. Once the thread
+/// stopped, the export stops too.
+#[derive(Component, Clone)]
+pub struct MmapExport {
+    pub path: PathBuf,
+    /// Number of index entries reserved at the start of the file.
+    pub max_frames: u32,
+    /// Frames buffered for the writer thread before new ones are dropped.
+    pub max_queued_frames: usize,
+    writer: Arc<Mutex<WriterState>>,
+}
+
+#[derive(Default)]
+enum WriterState {
+    /// No frame was exported yet.
+    #[default]
+    Idle,
+    Running(Sender<MmapFrame>),
+    /// The writer thread stopped, because the file is full or couldn't be written.
+    Stopped,
+}
+
+struct MmapFrame {
+    /// Shared with the other sinks of the frame.
+    image: Arc<Image>,
+    size: Extent3d,
+    format: u32,
+    render_frame: u32,
+}
+
+impl MmapExport {
+    pub fn new(path: impl Into<PathBuf>, max_frames: u32) -> Self {
+        Self {
+            path: path.into(),
+            max_frames,
+            max_queued_frames: 8,
+            writer: default(),
+        }
+    }
+
+    fn spawn_writer(&self) -> Sender<MmapFrame> {
+        let (sender, receiver) = bounded::<MmapFrame>(self.max_queued_frames);
+        let path = self.path.clone();
+        let max_frames = self.max_frames;
+        std::thread::spawn(move || {
+            let mut writer = match FrameFileWriter::create(&path, max_frames) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    error!("failed to create {}: {e}", path.display());
+                    return;
+                }
+            };
+            info!("started writing frames to {}", path.display());
+            for frame in receiver {
+                if writer.count == u64::from(max_frames) {
+                    warn!(
+                        "{} holds {max_frames} frames, dropping further frames",
+                        path.display()
+                    );
+                    return;
+                }
+                if let Err(e) = writer.append(&frame) {
+                    error!("failed to write to {}: {e}", path.display());
+                    return;
+                }
+            }
+        });
+        sender
+    }
+}
+
+struct FrameFileWriter {
+    file: File,
+    count: u64,
+    end: u64,
+}
+
+impl FrameFileWriter {
+    fn create(path: &Path, max_frames: u32) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let end = HEADER_SIZE + u64::from(max_frames) * ENTRY_SIZE;
+        file.set_len(end)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&max_frames.to_le_bytes())?;
+        Ok(Self {
+            file,
+            count: 0,
+            end,
+        })
+    }
+
+    fn append(&mut self, frame: &MmapFrame) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&frame.image.data)?;
+
+        let mut entry = Vec::with_capacity(ENTRY_SIZE as usize);
+        entry.extend(self.end.to_le_bytes());
+        entry.extend((frame.image.data.len() as u64).to_le_bytes());
+        entry.extend(frame.size.width.to_le_bytes());
+        entry.extend(frame.size.height.to_le_bytes());
+        entry.extend(frame.format.to_le_bytes());
+        entry.extend(frame.render_frame.to_le_bytes());
+        self.file
+            .seek(SeekFrom::Start(HEADER_SIZE + self.count * ENTRY_SIZE))?;
+        self.file.write_all(&entry)?;
+
+        // The count is updated last, so readers never see an entry without its frame.
+        self.count += 1;
+        self.end += frame.image.data.len() as u64;
+        self.file.seek(SeekFrom::Start(16))?;
+        self.file.write_all(&self.count.to_le_bytes())
+    }
+}
+
+impl ExtractComponent for MmapExport {
+    type Query = (&'static MmapExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (MmapExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some((this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct MmapExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: MmapExport,
+}
+
+pub(crate) fn write_to_mmap_files(
+    exports: Query<(Entity, &MmapExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    frame_count: Res<FrameCount>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (entity, export, source_handle) in &exports {
+        let id = source_handle.id();
+        if !source_is_present(id, &sources, &images, &mut missing_sources) {
+            continue;
+        }
+        let mut writer = export.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(*writer, WriterState::Stopped) {
+            continue;
+        }
+        let Some(frame) = frames.get_export_shared(entity, id) else {
+            continue;
+        };
+        let format = frame.texture_descriptor.format;
+        let Some(code) = FORMATS.iter().position(|&f| f == format) else {
+            warn!("cannot store frame of image source {id:?} with format {format:?}");
+            continue;
+        };
+
+        if matches!(*writer, WriterState::Idle) {
+            *writer = WriterState::Running(export.spawn_writer());
+        }
+        let WriterState::Running(sender) = &*writer else {
+            continue;
+        };
+        let frame = MmapFrame {
+            size: frame.texture_descriptor.size,
+            format: code as u32,
+            render_frame: frame_count.0,
+            image: frame,
+        };
+        match sender.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!(
+                    "dropping frame of image source {id:?}, {} is falling behind",
+                    export.path.display()
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!(
+                    "stopped writing frames of image source {id:?} to {}",
+                    export.path.display()
+                );
+                *writer = WriterState::Stopped;
+            }
+        }
+    }
+}
+
+/// Random access to the frames written by an [`MmapExport`], through a memory map of the
+/// file.
+///
+/// Open the file once its [`MmapExport`] finished writing it, e.g. after the export was
+/// despawned, see [`open`](Self::open).
+pub struct MmapFrameReader {
+    mmap: Mmap,
+    count: usize,
+}
+
+impl MmapFrameReader {
+    /// Maps the frame file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not change while the reader exists. An [`MmapExport`] still writing to
+    /// it rewrites the index inside the mapped range, and one starting to write to the same
+    /// path truncates the file, which can crash the process when the reader accesses it.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees that the file isn't modified while it's mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_SIZE as usize || &mmap[..8] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a frame file written by MmapExport",
+            ));
+        }
+        let count = u64::from_le_bytes(mmap[16..24].try_into().unwrap()) as usize;
+        let count = (0..count)
+            .take_while(|&index| read_entry(&mmap, index).is_some())
+            .count();
+        Ok(Self { mmap, count })
+    }
+
+    /// Number of frames in the file.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Render frame, as counted by [`FrameCount`], that frame `index` was captured in.
+    pub fn render_frame(&self, index: usize) -> Option<u32> {
+        self.entry(index).map(|entry| entry.render_frame)
+    }
+
+    /// The unpadded pixels of frame `index`, borrowed from the memory map.
+    pub fn frame_data(&self, index: usize) -> Option<&[u8]> {
+        let entry = self.entry(index)?;
+        Some(&self.mmap[entry.start..entry.end])
+    }
+
+    /// Copies frame `index` into an [`Image`].
+    pub fn frame(&self, index: usize) -> Option<Image> {
+        let entry = self.entry(index)?;
+        Some(Image::new(
+            entry.size,
+            TextureDimension::D2,
+            self.mmap[entry.start..entry.end].to_vec(),
+            entry.format,
+        ))
+    }
+
+    fn entry(&self, index: usize) -> Option<FrameEntry> {
+        if index >= self.count {
+            return None;
+        }
+        read_entry(&self.mmap, index)
+    }
+}
+
+/// Reads and validates the index entry of frame `index`.
+fn read_entry(mmap: &[u8], index: usize) -> Option<FrameEntry> {
+    let offset = usize::try_from(HEADER_SIZE + index as u64 * ENTRY_SIZE).ok()?;
+    let entry = mmap.get(offset..offset + ENTRY_SIZE as usize)?;
+    let u64_at = |at: usize| u64::from_le_bytes(entry[at..at + 8].try_into().unwrap());
+    let u32_at = |at: usize| u32::from_le_bytes(entry[at..at + 4].try_into().unwrap());
+    let start = usize::try_from(u64_at(0)).ok()?;
+    let len = usize::try_from(u64_at(8)).ok()?;
+    let end = start.checked_add(len)?;
+    let size = Extent3d {
+        width: u32_at(16),
+        height: u32_at(20),
+        depth_or_array_layers: 1,
+    };
+    let format = *FORMATS.get(u32_at(24) as usize)?;
+    let expected_len = size.width as u64 * size.height as u64 * u64::from(format.block_size(None)?);
+    if end > mmap.len() || len as u64 != expected_len {
+        return None;
+    }
+    Some(FrameEntry {
+        start,
+        end,
+        size,
+        format,
+        render_frame: u32_at(28),
+    })
+}
+
+struct FrameEntry {
+    start: usize,
+    end: usize,
+    size: Extent3d,
+    format: TextureFormat,
+    render_frame: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Removes the file when the test ends, even if it fails.
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn reads_back_any_written_frame() {
+        let file =
+            TempFile(std::env::temp_dir().join(format!("mmap_{}.frames", std::process::id())));
+        let mut writer = FrameFileWriter::create(&file.0, 16).unwrap();
+        let size = |n: u32| Extent3d {
+            width: n + 1,
+            height: 2,
+            depth_or_array_layers: 1,
+        };
+        for n in 0..10u32 {
+            let format = if n % 2 == 0 { 0 } else { 5 };
+            let len = size(n).width * 2 * FORMATS[format].block_size(None).unwrap();
+            let image = Image::new(
+                size(n),
+                TextureDimension::D2,
+                vec![n as u8; len as usize],
+                FORMATS[format],
+            );
+            let frame = MmapFrame {
+                image: Arc::new(image),
+                size: size(n),
+                format: format as u32,
+                render_frame: 100 + n,
+            };
+            writer.append(&frame).unwrap();
+        }
+        drop(writer);
+
+        // SAFETY: the file was written completely and isn't modified by the test.
+        let reader = unsafe { MmapFrameReader::open(&file.0) }.unwrap();
+        assert_eq!(reader.len(), 10);
+        for n in [7, 2, 9, 0] {
+            let frame = reader.frame(n as usize).unwrap();
+            let format = if n % 2 == 0 {
+                TextureFormat::Rgba8Unorm
+            } else {
+                TextureFormat::Rgba32Float
+            };
+            assert_eq!(frame.texture_descriptor.size, size(n));
+            assert_eq!(frame.texture_descriptor.format, format);
+            assert_eq!(
+                frame.data.len(),
+                (n as usize + 1) * 2 * format.block_size(None).unwrap() as usize
+            );
+            assert!(frame.data.iter().all(|&byte| byte == n as u8));
+            assert_eq!(reader.render_frame(n as usize), Some(100 + n));
+        }
+        assert!(reader.frame(10).is_none());
+    }
+}
@@ -0,0 +1,23 @@
+use image::{imageops::FilterType, DynamicImage, GrayImage};
+
+/// Size of the thumbnails frames are compared at.
+const THUMBNAIL_SIZE: u32 = 64;
+/// Brightness difference, out of 255, above which a thumbnail pixel counts as changed.
+const PIXEL_TOLERANCE: u8 = 12;
+
+/// Downscaled grayscale version of a frame, cheap to compare against others.
+pub(crate) fn motion_thumbnail(image: &DynamicImage) -> GrayImage {
+    image
+        .resize_exact(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle)
+        .into_luma8()
+}
+
+/// Fraction of the pixels that differ between two thumbnails.
+pub(crate) fn changed_fraction(a: &GrayImage, b: &GrayImage) -> f32 {
+    let changed = a
+        .pixels()
+        .zip(b.pixels())
+        .filter(|(a, b)| a.0[0].abs_diff(b.0[0]) > PIXEL_TOLERANCE)
+        .count();
+    changed as f32 / (THUMBNAIL_SIZE * THUMBNAIL_SIZE) as f32
+}
@@ -1,55 +1,459 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 use bevy::prelude::*;
+use bevy::utils::HashSet;
 use bevy::{
     ecs::query::QueryItem,
     render::{
         camera::CameraUpdateSystem,
         extract_component::{ExtractComponent, ExtractComponentPlugin},
-        main_graph::node::CAMERA_DRIVER,
-        render_asset::{RenderAssetPlugin, RenderAssets},
-        render_graph::RenderGraph,
-        renderer::RenderDevice,
-        Render, RenderApp, RenderSet,
+        render_asset::RenderAssets,
+        render_resource::TextureFormat,
+        Render, RenderApp,
     },
 };
-use ndi_sdk::send::{create_ndi_send_video_frame, FrameFormatType, SendColorFormat};
+use ndi_sdk::send::{
+    create_ndi_send_video_frame, FrameFormatType, NDISendVideoFrame, SendColorFormat,
+};
 use ndi_sdk::{load, SendInstance};
 
-use super::node::{ImageExportNode, NODE_NAME};
-use super::plugin::get_image;
-use super::plugin::ImageExportSource;
+use super::convert::convert_export_image;
+use super::plugin::{add_core_plugin, source_is_present, ExportedFrames, ImageExportRenderSystems};
+use super::plugin::{ImageExportSource, StreamingSink};
+use super::rate::RateLimiter;
 
 #[derive(Default)]
 pub struct NDIExportPlugin;
 
+/// Pixel layout of the frames sent over NDI.
+///
+/// 8-bit sRGB frames in the matching channel order are sent as they are, all others are
+/// converted to 8-bit sRGB first, like frames written to disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NDIColorFormat {
+    #[default]
+    Rgba,
+    /// Like `Rgba`, but receivers ignore the alpha channel.
+    Rgbx,
+    Bgra,
+    /// Like `Bgra`, but receivers ignore the alpha channel.
+    Bgrx,
+}
+
+impl NDIColorFormat {
+    fn send_color_format(self) -> SendColorFormat {
+        match self {
+            NDIColorFormat::Rgba => SendColorFormat::Rgba,
+            NDIColorFormat::Rgbx => SendColorFormat::Rgbx,
+            NDIColorFormat::Bgra => SendColorFormat::Bgra,
+            NDIColorFormat::Bgrx => SendColorFormat::Bgrx,
+        }
+    }
+
+    /// The 8-bit format frames are converted to before sending them.
+    fn texture_format(self) -> TextureFormat {
+        match self {
+            NDIColorFormat::Rgba | NDIColorFormat::Rgbx => TextureFormat::Rgba8UnormSrgb,
+            NDIColorFormat::Bgra | NDIColorFormat::Bgrx => TextureFormat::Bgra8UnormSrgb,
+        }
+    }
+
+    /// Whether frames of `format` are already encoded like this format and are sent as they
+    /// are.
+    fn sends_unconverted(self, format: TextureFormat) -> bool {
+        format == self.texture_format()
+    }
+
+    /// Converts `img` to the 8-bit layout of this format, unless it already has it.
+    fn convert<'a>(self, img: Cow<'a, Image>) -> Option<Cow<'a, Image>> {
+        if self.sends_unconverted(img.texture_descriptor.format) {
+            return Some(img);
+        }
+        convert_export_image(&img, self.texture_format()).map(Cow::Owned)
+    }
+}
+
+/// Scan type of the frames sent over NDI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NDIFrameFormat {
+    #[default]
+    Progressive,
+    Interleaved,
+    Field0,
+    Field1,
+}
+
+impl NDIFrameFormat {
+    fn frame_format_type(self) -> FrameFormatType {
+        match self {
+            NDIFrameFormat::Progressive => FrameFormatType::Progressive,
+            NDIFrameFormat::Interleaved => FrameFormatType::Interleaved,
+            NDIFrameFormat::Field0 => FrameFormatType::Field0,
+            NDIFrameFormat::Field1 => FrameFormatType::Field1,
+        }
+    }
+}
+
+/// Settings of an NDI sender, see [`NDIExport::with_config`].
+#[derive(Clone, Debug)]
+pub struct NDIConfig {
+    /// Source name shown to NDI receivers.
+    pub name: String,
+    /// Comma separated list of NDI groups the source is announced in.
+    pub groups: Option<String>,
+    /// Lets the SDK pace video sends to the frame rate.
+    pub clock_video: bool,
+    /// Lets the SDK pace audio sends to the sample rate.
+    pub clock_audio: bool,
+    pub color_format: NDIColorFormat,
+    pub frame_format: NDIFrameFormat,
+    /// Frame rate as numerator and denominator, e.g. `(30000, 1001)` for 29.97fps.
+    ///
+    /// Frames are sent at most at this rate and receivers are told the exact rate.
+    /// [`NDIExport::with_config`] fails if either number is 0.
+    pub frame_rate: (u32, u32),
+    /// Pads the rows of sent frames to a multiple of this many bytes, for receivers or SDK
+    /// versions that perform better with aligned lines.
+    ///
+    /// Sources with [`ImageExportSource::set_keep_padding`] are sent with the GPU's row
+    /// padding of 256 bytes as the stride, without repacking the frame on the CPU, which
+    /// satisfies any alignment dividing 256. Other frames are repacked.
+    pub stride_alignment: Option<u32>,
+    /// Frames waiting for the send thread, which falls behind e.g. when the SDK paces sends
+    /// with `clock_video`. Beyond this count the oldest queued frame is dropped, bounding the
+    /// latency. At least 1.
+    pub max_queued_frames: usize,
+    /// Publishes the number of dropped frames, see [`NDIExport::dropped_frames`], as NDI
+    /// metadata at most this often, e.g. for downstream monitoring to flag an overloaded
+    /// sender. The count is sent to connected receivers as
+    /// `<bevy_image_export dropped_frames="12"/>` and kept as connection metadata for new ones.
+    pub dropped_frames_metadata_interval: Option<Duration>,
+}
+
+impl Default for NDIConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            groups: None,
+            clock_video: false,
+            clock_audio: false,
+            color_format: NDIColorFormat::default(),
+            frame_format: NDIFrameFormat::default(),
+            frame_rate: (60, 1),
+            stride_alignment: None,
+            max_queued_frames: 2,
+            dropped_frames_metadata_interval: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+impl NDIConfig {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..default()
+        }
+    }
+}
+
+/// An NDI send instance along with the frame it sends asynchronously.
+///
+/// The SDK keeps reading a frame passed to an async send until the next send, so the frame is
+/// kept alive until then.
+struct NDISender {
+    instance: SendInstance,
+    in_flight: Option<NDISendVideoFrame>,
+}
+
+impl NDISender {
+    /// Sends `frame`, returning the previous frame, which the SDK released when it received
+    /// this one.
+    fn send(&mut self, frame: NDISendVideoFrame) -> Option<NDISendVideoFrame> {
+        self.instance.send_video_async(&frame);
+        self.in_flight.replace(frame)
+    }
+
+    /// Sends the dropped frame count to connected receivers and replaces the connection
+    /// metadata sent to new ones.
+    fn publish_dropped_frames(&mut self, dropped: u64) {
+        let metadata = format!("<bevy_image_export dropped_frames=\"{dropped}\"/>");
+        self.instance.send_metadata(&metadata);
+        self.instance.clear_connection_metadata();
+        self.instance.add_connection_metadata(&metadata);
+    }
+}
+
+impl Drop for NDISender {
+    fn drop(&mut self) {
+        if self.in_flight.is_some() {
+            self.instance.flush_async_video();
+        }
+    }
+}
+
+/// Frames waiting for the send thread of an [`NDIExport`], oldest first.
+///
+/// The data of sent and dropped frames is reused for the next frame, so that buffers are
+/// rarely allocated.
+struct NDIQueue {
+    frames: Mutex<VecDeque<NDISendVideoFrame>>,
+    queued: Condvar,
+    max_queued_frames: usize,
+    dropped: AtomicU64,
+    /// Receivers connected to the sender, queried by the send thread after every frame, so that
+    /// reading it never waits for a clocked send.
+    connections: AtomicUsize,
+    closed: AtomicBool,
+    spare: Mutex<Vec<u8>>,
+}
+
+impl NDIQueue {
+    /// Takes the buffer for the next frame, filled with `data`.
+    fn buffer(&self, data: &[u8]) -> Vec<u8> {
+        let mut buffer = std::mem::take(&mut *self.spare.lock().unwrap_or_else(|e| e.into_inner()));
+        buffer.clear();
+        buffer.extend_from_slice(data);
+        buffer
+    }
+
+    /// Like [`buffer`](Self::buffer), but copies the rows of `row_bytes` bytes stored every
+    /// `stride` bytes in `data` to every `new_stride` bytes.
+    fn buffer_restrided(
+        &self,
+        data: &[u8],
+        row_bytes: usize,
+        stride: usize,
+        new_stride: usize,
+    ) -> Vec<u8> {
+        let mut buffer = std::mem::take(&mut *self.spare.lock().unwrap_or_else(|e| e.into_inner()));
+        buffer.clear();
+        for row in data.chunks(stride) {
+            let row = &row[..row_bytes.min(row.len())];
+            buffer.extend_from_slice(row);
+            buffer.resize(buffer.len() + new_stride - row.len(), 0);
+        }
+        buffer
+    }
+
+    fn recycle(&self, frame: NDISendVideoFrame) {
+        *self.spare.lock().unwrap_or_else(|e| e.into_inner()) = frame.into_data();
+    }
+
+    /// Queues `frame` for the send thread, dropping the oldest frames beyond the limit.
+    fn push(&self, frame: NDISendVideoFrame) {
+        let mut frames = self.frames.lock().unwrap_or_else(|e| e.into_inner());
+        frames.push_back(frame);
+        while frames.len() > self.max_queued_frames {
+            let Some(dropped) = frames.pop_front() else {
+                break;
+            };
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            self.recycle(dropped);
+        }
+        drop(frames);
+        self.queued.notify_one();
+    }
+
+    /// Waits for the next frame, or returns `None` once the export is dropped.
+    fn pop(&self) -> Option<NDISendVideoFrame> {
+        let mut frames = self.frames.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if self.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            if let Some(frame) = frames.pop_front() {
+                return Some(frame);
+            }
+            frames = self.queued.wait(frames).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Stops the send thread once all clones of its [`NDIExport`] are dropped.
+struct NDISendThread {
+    queue: Arc<NDIQueue>,
+}
+
+impl NDISendThread {
+    fn spawn(
+        mut sender: NDISender,
+        max_queued_frames: usize,
+        metadata_interval: Option<Duration>,
+    ) -> Self {
+        let queue = Arc::new(NDIQueue {
+            frames: default(),
+            queued: default(),
+            max_queued_frames: max_queued_frames.max(1),
+            dropped: default(),
+            connections: default(),
+            closed: default(),
+            spare: default(),
+        });
+        let thread_queue = queue.clone();
+        std::thread::spawn(move || {
+            let mut metadata_limiter = metadata_interval.map(RateLimiter::new);
+            let mut published_dropped = None;
+            while let Some(frame) = thread_queue.pop() {
+                let previous = sender.send(frame);
+                if let Some(previous) = previous {
+                    thread_queue.recycle(previous);
+                }
+                let connections = sender.instance.get_no_connections(0).max(0) as usize;
+                thread_queue
+                    .connections
+                    .store(connections, Ordering::Relaxed);
+                let dropped = thread_queue.dropped.load(Ordering::Relaxed);
+                if published_dropped != Some(dropped)
+                    && metadata_limiter
+                        .as_mut()
+                        .is_some_and(|limiter| limiter.ready(Instant::now()))
+                {
+                    sender.publish_dropped_frames(dropped);
+                    published_dropped = Some(dropped);
+                }
+            }
+        });
+        Self { queue }
+    }
+}
+
+impl Drop for NDISendThread {
+    fn drop(&mut self) {
+        // Locking orders the flag before the send thread's next check, so the wakeup can't be
+        // missed.
+        let _frames = self.queue.frames.lock().unwrap_or_else(|e| e.into_inner());
+        self.queue.closed.store(true, Ordering::Release);
+        self.queue.queued.notify_all();
+    }
+}
+
 #[derive(Component, Clone)]
 pub struct NDIExport {
-    sender: Arc<Mutex<SendInstance>>,
+    color_format: NDIColorFormat,
+    frame_format: NDIFrameFormat,
+    frame_rate: (u32, u32),
+    stride_alignment: Option<u32>,
+    send_thread: Arc<NDISendThread>,
+    /// Paces sends to the frame rate.
+    rate_limiter: Arc<Mutex<RateLimiter>>,
 }
 
 impl NDIExport {
     pub fn new(instance_name: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let sender = match load() {
-            Err(e) => return Err(format!("failed to load NDI SDK: {e}").into()),
-            Ok(instance) => match instance.create_send_instance(instance_name, false, false) {
-                Err(e) => return Err(format!("failed to create NDI send instance: {e}").into()),
-                Ok(sender) => sender,
-            },
+        Self::with_config(NDIConfig::new(instance_name))
+    }
+
+    pub fn with_config(config: NDIConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let (numerator, denominator) = config.frame_rate;
+        if numerator == 0 || denominator == 0 {
+            return Err(format!("invalid NDI frame rate {numerator}/{denominator}").into());
+        }
+        let instance = load().map_err(|e| format!("failed to load NDI SDK: {e}"))?;
+        let sender = match config.groups {
+            Some(groups) => instance.create_send_instance_with_groups(
+                config.name,
+                groups,
+                config.clock_video,
+                config.clock_audio,
+            ),
+            None => {
+                instance.create_send_instance(config.name, config.clock_video, config.clock_audio)
+            }
+        }
+        .map_err(|e| format!("failed to create NDI send instance: {e}"))?;
+        let sender = NDISender {
+            instance: sender,
+            in_flight: None,
         };
         Ok(Self {
-            sender: Arc::new(Mutex::new(sender)),
+            send_thread: Arc::new(NDISendThread::spawn(
+                sender,
+                config.max_queued_frames,
+                config.dropped_frames_metadata_interval,
+            )),
+            color_format: config.color_format,
+            frame_format: config.frame_format,
+            frame_rate: config.frame_rate,
+            stride_alignment: config.stride_alignment.filter(|&alignment| alignment > 0),
+            rate_limiter: Arc::new(Mutex::new(RateLimiter::from_frame_rate(config.frame_rate))),
         })
     }
+
+    /// Number of NDI receivers connected to this sender when it last sent a frame.
+    pub fn connections(&self) -> usize {
+        self.send_thread.queue.connections.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames waiting for the send thread.
+    pub fn queued_frames(&self) -> usize {
+        self.send_thread
+            .queue
+            .frames
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Total number of frames dropped because the send thread fell behind by more than
+    /// [`NDIConfig::max_queued_frames`].
+    pub fn dropped_frames(&self) -> u64 {
+        self.send_thread.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether the NDI runtime could be loaded when [`NDIExportPlugin`] was added.
+///
+/// Without it the plugin only registers [`NDIStatus`] and this resource, and
+/// [`NDIExport::new`] fails.
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub enum NDIRuntime {
+    Available,
+    Unavailable { reason: String },
+}
+
+impl NDIRuntime {
+    pub fn is_available(&self) -> bool {
+        *self == NDIRuntime::Available
+    }
+}
+
+/// Mirrors the state of all NDI senders into the main world, updated every frame.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct NDIStatus {
+    /// Total number of receivers connected to any [`NDIExport`], see [`NDIExport::connections`].
+    pub connections: usize,
+    /// Total number of frames any [`NDIExport`] dropped, see [`NDIExport::dropped_frames`].
+    pub dropped_frames: u64,
+}
+
+fn update_ndi_status(ndi_exports: Query<&NDIExport>, mut status: ResMut<NDIStatus>) {
+    let connections = ndi_exports.iter().map(NDIExport::connections).sum();
+    if status.connections != connections {
+        debug!("NDI receiver count changed to {connections}");
+        status.connections = connections;
+    }
+    let dropped_frames = ndi_exports.iter().map(NDIExport::dropped_frames).sum();
+    if status.dropped_frames != dropped_frames {
+        debug!("NDI exports dropped {dropped_frames} frames in total");
+        status.dropped_frames = dropped_frames;
+    }
 }
 
 impl ExtractComponent for NDIExport {
     type Query = (&'static NDIExport, &'static Handle<ImageExportSource>);
     type Filter = ();
-    type Out = (NDIExport, Handle<ImageExportSource>);
+    type Out = (NDIExport, Handle<ImageExportSource>, StreamingSink);
 
     fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
-        Some((this.clone(), source_handle.clone_weak()))
+        Some((this.clone(), source_handle.clone(), StreamingSink))
     }
 }
 
@@ -65,38 +469,106 @@ pub enum NDIExportSystems {
     SetupNDIExportFlush,
 }
 
+/// The stride frames with rows of `row_bytes` bytes stored every `stride` bytes are sent with:
+/// `stride` if it is a multiple of `alignment`, or else `row_bytes` padded to one. An
+/// alignment of 0 is ignored.
+fn line_stride(row_bytes: u32, stride: u32, alignment: Option<u32>) -> u32 {
+    match alignment.filter(|&alignment| alignment > 0) {
+        Some(alignment) if !stride.is_multiple_of(alignment) => {
+            row_bytes.next_multiple_of(alignment)
+        }
+        _ => stride,
+    }
+}
+
 fn ndi_send_buffer(
-    ndi_export_bundle: Query<(Ref<NDIExport>, Ref<Handle<ImageExportSource>>)>,
+    ndi_export_bundle: Query<(Entity, Ref<NDIExport>, Ref<Handle<ImageExportSource>>)>,
     sources: Res<RenderAssets<ImageExportSource>>,
-    render_device: Res<RenderDevice>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+    mut started_sources: Local<HashSet<AssetId<ImageExportSource>>>,
 ) {
     let sources = sources.into_inner();
-    let render_device = render_device.into_inner();
+    let images = images.into_inner();
 
-    for (ndi_export, source_handle) in &ndi_export_bundle {
-        if let Some(img) = get_image(source_handle.clone(), sources, render_device) {
+    for (entity, ndi_export, source_handle) in &ndi_export_bundle {
+        let id = source_handle.id();
+        if !source_is_present(id, sources, images, &mut missing_sources) {
+            continue;
+        }
+        // Frames that weren't read back yet don't use up a send slot.
+        if frames.get_export(entity, id).is_none() {
+            continue;
+        }
+        if !ndi_export
+            .rate_limiter
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .ready(Instant::now())
+        {
+            continue;
+        }
+        let color_format = ndi_export.color_format;
+        // Padded frames are only sent as they are if they need no conversion.
+        let stride = frames.export_padded_bytes_per_row(entity, id).filter(|_| {
+            frames
+                .get_export(entity, id)
+                .is_some_and(|img| color_format.sends_unconverted(img.texture_descriptor.format))
+        });
+        let frame = match stride {
+            Some(_) => frames.get_export(entity, id).map(Cow::Borrowed),
+            None => frames.get_export_unpadded(entity, id),
+        };
+        if let Some(img) = frame {
+            if started_sources.insert(id) {
+                info!("started sending image source {id:?} over NDI");
+            }
+            if let Some(stride) = stride {
+                trace!("sending padded frame of image source {id:?} with a stride of {stride}");
+            }
+            let Some(img) = color_format.convert(img) else {
+                error!("failed to convert frame of image source {id:?} to {color_format:?}");
+                continue;
+            };
+            let queue = &ndi_export.send_thread.queue;
             let (x, y) = (img.width() as i32, img.height() as i32);
-            let frame_builder = create_ndi_send_video_frame(x, y, FrameFormatType::Progressive)
-                .with_data(img.data, x * 4, SendColorFormat::Rgba);
+            let bytes_per_pixel = img.texture_descriptor.format.block_size(None).unwrap_or(4);
+            let row_bytes = img.width() * bytes_per_pixel;
+            let stride = stride.unwrap_or(row_bytes);
+            let line_stride = line_stride(row_bytes, stride, ndi_export.stride_alignment);
+            let data = if line_stride == stride {
+                queue.buffer(&img.data)
+            } else {
+                queue.buffer_restrided(
+                    &img.data,
+                    row_bytes as usize,
+                    stride as usize,
+                    line_stride as usize,
+                )
+            };
+            let (numerator, denominator) = ndi_export.frame_rate;
+            let frame_builder =
+                create_ndi_send_video_frame(x, y, ndi_export.frame_format.frame_format_type())
+                    .with_data(
+                        data,
+                        line_stride as i32,
+                        ndi_export.color_format.send_color_format(),
+                    )
+                    .with_frame_rate(numerator as i32, denominator as i32);
             let frame = match frame_builder.build() {
                 Err(e) => {
-                    eprintln!("failed to build NDISendVideoFrame: {e}");
+                    error!("failed to build NDISendVideoFrame: {e}");
                     return;
                 }
                 Ok(f) => f,
             };
-            ndi_export
-                .sender
-                .lock()
-                .unwrap_or_else(|e| e.into_inner())
-                .send_video(frame);
+            trace!("queueing {x}x{y} frame of image source {id:?} for NDI");
+            queue.push(frame);
         }
     }
 }
 
-#[derive(Resource)]
-struct NDIExportRateLimiter(Timer);
-
 impl Plugin for NDIExportPlugin {
     fn build(&self, app: &mut App) {
         app.configure_sets(
@@ -108,26 +580,88 @@ impl Plugin for NDIExportPlugin {
                 .chain()
                 .before(CameraUpdateSystem),
         )
-        .register_type::<ImageExportSource>()
-        .init_asset::<ImageExportSource>()
-        .register_asset_reflect::<ImageExportSource>()
-        .add_plugins((
-            RenderAssetPlugin::<ImageExportSource>::default(),
-            ExtractComponentPlugin::<NDIExport>::default(),
-        ));
+        .init_resource::<NDIStatus>();
+
+        if let Err(e) = load() {
+            warn!("NDI runtime not found, NDI export is disabled: {e}");
+            app.insert_resource(NDIRuntime::Unavailable {
+                reason: e.to_string(),
+            });
+            return;
+        }
+        add_core_plugin(app);
 
-        let render_app = app.sub_app_mut(RenderApp);
+        app.insert_resource(NDIRuntime::Available)
+            .add_systems(PreUpdate, update_ndi_status)
+            .add_plugins(ExtractComponentPlugin::<NDIExport>::default());
 
-        render_app.add_systems(
+        app.sub_app_mut(RenderApp).add_systems(
             Render,
-            ndi_send_buffer
-                .after(RenderSet::Render)
-                .before(RenderSet::Cleanup),
+            ndi_send_buffer.in_set(ImageExportRenderSystems::Export),
         );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
 
-        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+    use super::*;
+
+    fn image(format: TextureFormat, data: Vec<u8>) -> Image {
+        Image::new(
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            format,
+        )
+    }
 
-        graph.add_node(NODE_NAME, ImageExportNode);
-        graph.add_node_edge(CAMERA_DRIVER, NODE_NAME);
+    #[test]
+    fn line_stride_is_padded_to_the_alignment() {
+        assert_eq!(line_stride(12, 12, None), 12);
+        assert_eq!(line_stride(12, 12, Some(0)), 12);
+        assert_eq!(line_stride(12, 12, Some(16)), 16);
+        assert_eq!(line_stride(12, 256, Some(64)), 256);
+        assert_eq!(line_stride(12, 256, Some(384)), 384);
+    }
+
+    #[test]
+    fn float_frames_are_sent_as_8_bit_rgba() {
+        let one = 1.0f32.to_le_bytes();
+        let src = image(TextureFormat::Rgba32Float, one.repeat(4));
+        let sent = NDIColorFormat::Rgba.convert(Cow::Borrowed(&src)).unwrap();
+        assert_eq!(
+            sent.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(sent.data, [255; 4]);
+
+        let srgb = image(TextureFormat::Rgba8UnormSrgb, vec![1, 2, 3, 4]);
+        let sent = NDIColorFormat::Rgbx.convert(Cow::Borrowed(&srgb)).unwrap();
+        assert!(matches!(sent, Cow::Borrowed(_)));
+
+        // Linear frames are sRGB encoded whatever the channel order.
+        let linear = image(TextureFormat::Rgba8Unorm, vec![128, 0, 255, 255]);
+        let rgba = NDIColorFormat::Rgba
+            .convert(Cow::Borrowed(&linear))
+            .unwrap();
+        assert_eq!(
+            rgba.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+        assert_eq!(rgba.data, [188, 0, 255, 255]);
+        let bgra = NDIColorFormat::Bgra
+            .convert(Cow::Borrowed(&linear))
+            .unwrap();
+        assert_eq!(
+            bgra.texture_descriptor.format,
+            TextureFormat::Bgra8UnormSrgb
+        );
+        assert_eq!(bgra.data, [255, 0, 188, 255]);
     }
 }
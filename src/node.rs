@@ -1,31 +1,236 @@
+use std::sync::atomic::Ordering;
+
+use crate::destination::GpuDestinations;
 use crate::ImageExportSource;
 use bevy::{
+    core::FrameCount,
+    core_pipeline::{
+        blit::BlitPipeline,
+        core_2d::CORE_2D,
+        core_3d::{
+            graph::node::{END_MAIN_PASS_POST_PROCESSING, UPSCALING},
+            CORE_3D,
+        },
+    },
     prelude::*,
     render::{
+        camera::{ExtractedCamera, NormalizedRenderTarget},
         render_asset::RenderAssets,
-        render_graph::{Node, NodeRunError, RenderGraphContext},
-        render_resource::{ImageCopyBuffer, ImageDataLayout},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            BindGroupEntries, ImageCopyBuffer, ImageDataLayout, LoadOp, Operations, PipelineCache,
+            RenderPassColorAttachment, RenderPassDescriptor,
+        },
         renderer::RenderContext,
+        view::ViewTarget,
     },
 };
 
 pub const NODE_NAME: &str = "image_export";
+/// Node in the camera sub graphs copying the sources captured after a specific camera, see
+/// [`ImageExportSource::set_capture_after_camera`].
+pub const CAMERA_NODE_NAME: &str = "image_export_after_camera";
+
+/// Adds the node copying export sources into their readback buffers to `graph`, running after
+/// all nodes in `after` and before all nodes in `before`.
+///
+/// The plugins add it right after the camera driver. Use this to place it elsewhere in a custom
+/// graph, e.g. after an upscaling node so that the upscaled result is exported. A node added
+/// before, e.g. by a plugin, is replaced together with its edges.
+pub fn add_image_export_node(
+    graph: &mut RenderGraph,
+    after: &[&'static str],
+    before: &[&'static str],
+) {
+    add_image_export_node_named(graph, NODE_NAME, after, before);
+}
+
+/// Like [`add_image_export_node`], but adds a node called `name` that only copies the sources
+/// assigned to it with [`ImageExportSource::set_node`].
+///
+/// Multiple nodes can export the same image at different points of the graph. Added to a
+/// camera's sub graph (e.g. `core_3d`), the node copies the camera's intermediate texture
+/// instead of its target image, e.g. to export the scene without UI by running before
+/// `ui_pass`.
+pub fn add_image_export_node_named(
+    graph: &mut RenderGraph,
+    name: &'static str,
+    after: &[&'static str],
+    before: &[&'static str],
+) {
+    let _ = graph.remove_node(name);
+    graph.add_node(
+        name,
+        ImageExportNode {
+            name,
+            after_camera: false,
+        },
+    );
+    for &node in after {
+        graph.add_node_edge(node, name);
+    }
+    for &node in before {
+        graph.add_node_edge(name, node);
+    }
+}
+
+/// Name of the UI pass in the camera sub graphs, if `bevy_ui` is used.
+const UI_PASS: &str = "ui_pass";
+
+/// Adds the node copying sources captured after a specific camera to the end of the 2D and 3D
+/// camera sub graphs, after the UI if there is one but before upscaling into the target.
+///
+/// The edges are only added to nodes that exist when this is called, so it must run once all
+/// plugins adding nodes to the camera sub graphs are built, i.e. in [`Plugin::finish`].
+pub(crate) fn add_camera_export_nodes(graph: &mut RenderGraph) {
+    for sub_graph in [CORE_2D, CORE_3D] {
+        let Some(graph) = graph.get_sub_graph_mut(sub_graph) else {
+            continue;
+        };
+        let _ = graph.remove_node(CAMERA_NODE_NAME);
+        graph.add_node(
+            CAMERA_NODE_NAME,
+            ImageExportNode {
+                name: CAMERA_NODE_NAME,
+                after_camera: true,
+            },
+        );
+        for node in [END_MAIN_PASS_POST_PROCESSING, UI_PASS] {
+            if graph.get_node_state(node).is_ok() {
+                graph.add_node_edge(node, CAMERA_NODE_NAME);
+            }
+        }
+        if graph.get_node_state(UPSCALING).is_ok() {
+            graph.add_node_edge(CAMERA_NODE_NAME, UPSCALING);
+        }
+    }
+}
+
+/// The target image and intermediate texture of a camera rendering to an image.
+fn image_view_target(world: &World, view: Entity) -> Option<(AssetId<Image>, &ViewTarget)> {
+    let camera = world.get::<ExtractedCamera>(view)?;
+    let Some(NormalizedRenderTarget::Image(target)) = &camera.target else {
+        return None;
+    };
+    Some((target.id(), world.get::<ViewTarget>(view)?))
+}
+
+pub struct ImageExportNode {
+    name: &'static str,
+    /// Copies the sources captured after the camera whose sub graph runs the node, instead of
+    /// the sources assigned to the node.
+    after_camera: bool,
+}
 
-pub struct ImageExportNode;
 impl Node for ImageExportNode {
     fn run(
         &self,
-        _graph: &mut RenderGraphContext,
+        graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        for (_, source) in world.resource::<RenderAssets<ImageExportSource>>().iter() {
+        let frame_count = world.resource::<FrameCount>().0;
+        // Inside a camera's sub graph the frame isn't in the target image yet, it's copied from
+        // the camera's intermediate texture instead.
+        let view = match graph.get_view_entity() {
+            Some(view) => match image_view_target(world, view) {
+                Some(view) => Some(view),
+                None => return Ok(()),
+            },
+            None => None,
+        };
+
+        for (id, source) in world.resource::<RenderAssets<ImageExportSource>>().iter() {
+            let assigned = if self.after_camera {
+                source.capture_after_camera.is_some()
+                    && source.capture_after_camera == graph.get_view_entity()
+            } else {
+                source.capture_after_camera.is_none() && source.node == self.name
+            };
+            if !assigned {
+                continue;
+            }
             if let Some(gpu_image) = world
                 .resource::<RenderAssets<Image>>()
                 .get(&source.source_handle)
             {
+                let (texture, texture_view) = match (&source.resolve_target, view) {
+                    (_, Some((target, view_target))) => {
+                        if target != source.source_handle.id() {
+                            continue;
+                        }
+                        if view_target.main_texture_format() != gpu_image.texture_format {
+                            warn!(
+                                "skipping export of {:?} at node {}, the camera's {:?} intermediate texture doesn't match the image format {:?}",
+                                source.source_handle.id(),
+                                self.name,
+                                view_target.main_texture_format(),
+                                gpu_image.texture_format
+                            );
+                            continue;
+                        }
+                        (view_target.main_texture(), view_target.main_texture_view())
+                    }
+                    (Some((resolve_texture, resolve_view)), None) => {
+                        // Multisampled textures can't be copied, resolve them with an empty
+                        // render pass first.
+                        render_context
+                            .command_encoder()
+                            .begin_render_pass(&RenderPassDescriptor {
+                                label: Some("image_export_resolve"),
+                                color_attachments: &[Some(RenderPassColorAttachment {
+                                    view: &gpu_image.texture_view,
+                                    resolve_target: Some(resolve_view),
+                                    ops: Operations {
+                                        load: LoadOp::Load,
+                                        store: true,
+                                    },
+                                })],
+                                depth_stencil_attachment: None,
+                            });
+                        (resolve_texture, resolve_view)
+                    }
+                    (None, None) => (&gpu_image.texture, &gpu_image.texture_view),
+                };
+                let texture = match &source.preview {
+                    Some(preview) => {
+                        let Some(pipeline) = world
+                            .resource::<PipelineCache>()
+                            .get_render_pipeline(preview.pipeline)
+                        else {
+                            // The pipeline is still compiling, the readback skips the frame.
+                            source.skipped_frame.store(frame_count, Ordering::Release);
+                            continue;
+                        };
+                        let blit_pipeline = world.resource::<BlitPipeline>();
+                        let bind_group = render_context.render_device().create_bind_group(
+                            None,
+                            &blit_pipeline.texture_bind_group,
+                            &BindGroupEntries::sequential((texture_view, &blit_pipeline.sampler)),
+                        );
+                        let mut render_pass = render_context.command_encoder().begin_render_pass(
+                            &RenderPassDescriptor {
+                                label: Some("image_export_preview"),
+                                color_attachments: &[Some(RenderPassColorAttachment {
+                                    view: &preview.view,
+                                    resolve_target: None,
+                                    ops: Operations {
+                                        load: LoadOp::Load,
+                                        store: true,
+                                    },
+                                })],
+                                depth_stencil_attachment: None,
+                            },
+                        );
+                        render_pass.set_pipeline(pipeline);
+                        render_pass.set_bind_group(0, &bind_group, &[]);
+                        render_pass.draw(0..3, 0..1);
+                        &preview.texture
+                    }
+                    None => texture,
+                };
                 render_context.command_encoder().copy_texture_to_buffer(
-                    gpu_image.texture.as_image_copy(),
+                    texture.as_image_copy(),
                     ImageCopyBuffer {
                         buffer: &source.buffer,
                         layout: ImageDataLayout {
@@ -36,6 +241,28 @@ impl Node for ImageExportNode {
                     },
                     source.source_size,
                 );
+                let destinations = world
+                    .get_resource::<GpuDestinations>()
+                    .and_then(|destinations| destinations.0.get(&id));
+                for &destination in destinations.into_iter().flatten() {
+                    let Some(destination) =
+                        world.resource::<RenderAssets<Image>>().get(destination)
+                    else {
+                        continue;
+                    };
+                    // Resized in the main world, the image may lag behind for a frame.
+                    if destination.texture.size() != source.source_size
+                        || destination.texture_format != texture.format()
+                    {
+                        continue;
+                    }
+                    render_context.command_encoder().copy_texture_to_texture(
+                        texture.as_image_copy(),
+                        destination.texture.as_image_copy(),
+                        source.source_size,
+                    );
+                }
+                source.copied_frame.store(frame_count, Ordering::Release);
             }
         }
 
@@ -0,0 +1,251 @@
+use bevy::tasks::ComputeTaskPool;
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use image::DynamicImage;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+/// Largest IDAT chunk written, well below the 2^31 - 1 bytes PNG allows.
+const MAX_CHUNK_SIZE: usize = 1 << 24;
+
+/// Encodes `image` into a single PNG file, filtering and compressing `bands` horizontal bands of
+/// rows in parallel on the [`ComputeTaskPool`].
+///
+/// The bands are compressed into independent deflate blocks, joined into one zlib stream, so the
+/// file decodes to exactly the pixels of `image` and doesn't depend on how the bands were
+/// scheduled. It's slightly larger than one written by a single-threaded encoder, as matches
+/// can't reach back into the previous band. Returns `None` for images that aren't 8 or 16-bit
+/// integer images, which have no PNG color type.
+pub(crate) fn encode_png_bands(image: &DynamicImage, bands: u32) -> Option<Vec<u8>> {
+    encode_png(image, bands, true)
+}
+
+fn encode_png(image: &DynamicImage, bands: u32, parallel: bool) -> Option<Vec<u8>> {
+    let (color_type, bit_depth) = match image {
+        DynamicImage::ImageLuma8(_) => (0, 8),
+        DynamicImage::ImageRgb8(_) => (2, 8),
+        DynamicImage::ImageLumaA8(_) => (4, 8),
+        DynamicImage::ImageRgba8(_) => (6, 8),
+        DynamicImage::ImageLuma16(_) => (0, 16),
+        DynamicImage::ImageRgb16(_) => (2, 16),
+        DynamicImage::ImageLumaA16(_) => (4, 16),
+        DynamicImage::ImageRgba16(_) => (6, 16),
+        _ => return None,
+    };
+    let (width, height) = (image.width(), image.height());
+    // PNG stores 16-bit samples big-endian.
+    let samples = match bit_depth {
+        8 => image.as_bytes().to_vec(),
+        _ => image
+            .as_bytes()
+            .chunks_exact(2)
+            .flat_map(|sample| u16::from_ne_bytes([sample[0], sample[1]]).to_be_bytes())
+            .collect(),
+    };
+    let pixel_size = image.color().bytes_per_pixel() as usize;
+    let row_size = width as usize * pixel_size;
+
+    let bands = bands.clamp(1, height.max(1)) as usize;
+    let band_rows = (height as usize).div_ceil(bands).max(1);
+    let rows: Vec<_> = samples.chunks(row_size.max(1)).collect();
+    let compress_band = |band: usize| {
+        let first = band * band_rows;
+        let last = (first + band_rows).min(rows.len());
+        let mut filtered = Vec::with_capacity((last - first) * (row_size + 1));
+        for y in first..last {
+            filter_paeth(
+                rows[y],
+                y.checked_sub(1).map(|y| rows[y]),
+                pixel_size,
+                &mut filtered,
+            );
+        }
+        let last_band = last == rows.len();
+        (
+            deflate(&filtered, last_band),
+            adler32(&filtered),
+            filtered.len(),
+        )
+    };
+    let band_count = rows.len().div_ceil(band_rows).max(1);
+    let compressed: Vec<_> = if parallel {
+        ComputeTaskPool::get().scope(|scope| {
+            for band in 0..band_count {
+                let compress_band = &compress_band;
+                scope.spawn(async move { compress_band(band) });
+            }
+        })
+    } else {
+        (0..band_count).map(compress_band).collect()
+    };
+
+    // zlib header for deflate with a 32K window, followed by the joined bands and the Adler-32
+    // of the whole filtered image.
+    let mut zlib = vec![0x78, 0x9c];
+    let mut checksum = 1;
+    for (deflated, band_checksum, len) in &compressed {
+        zlib.extend_from_slice(deflated);
+        checksum = adler32_combine(checksum, *band_checksum, *len);
+    }
+    zlib.extend_from_slice(&checksum.to_be_bytes());
+
+    let mut png = SIGNATURE.to_vec();
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth and color type, followed by the default compression, filter and interlace
+    // methods.
+    header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+    for chunk in zlib.chunks(MAX_CHUNK_SIZE) {
+        write_chunk(&mut png, b"IDAT", chunk);
+    }
+    write_chunk(&mut png, b"IEND", &[]);
+    Some(png)
+}
+
+/// Appends `row` with the Paeth filter applied, preceded by its filter type.
+fn filter_paeth(row: &[u8], previous: Option<&[u8]>, pixel_size: usize, filtered: &mut Vec<u8>) {
+    filtered.push(4);
+    for (x, &value) in row.iter().enumerate() {
+        let left = x.checked_sub(pixel_size).map_or(0, |x| row[x]);
+        let (up, up_left) = match previous {
+            Some(previous) => (
+                previous[x],
+                x.checked_sub(pixel_size).map_or(0, |x| previous[x]),
+            ),
+            None => (0, 0),
+        };
+        let estimate = left as i16 + up as i16 - up_left as i16;
+        let (to_left, to_up, to_up_left) = (
+            (estimate - left as i16).abs(),
+            (estimate - up as i16).abs(),
+            (estimate - up_left as i16).abs(),
+        );
+        let predictor = if to_left <= to_up && to_left <= to_up_left {
+            left
+        } else if to_up <= to_up_left {
+            up
+        } else {
+            up_left
+        };
+        filtered.push(value.wrapping_sub(predictor));
+    }
+}
+
+/// Compresses `data` into raw deflate blocks, ending the stream if `last`, or byte-aligned so
+/// that the blocks of the next band can follow.
+fn deflate(data: &[u8], last: bool) -> Vec<u8> {
+    let mut compress = Compress::new(Compression::default(), false);
+    let flush = if last {
+        FlushCompress::Finish
+    } else {
+        FlushCompress::Sync
+    };
+    let mut deflated = Vec::with_capacity(data.len() / 2 + 64);
+    loop {
+        if deflated.len() == deflated.capacity() {
+            deflated.reserve(deflated.capacity());
+        }
+        let input = &data[compress.total_in() as usize..];
+        let status = compress
+            .compress_vec(input, &mut deflated, flush)
+            .expect("deflate never fails on valid input");
+        let consumed = compress.total_in() as usize == data.len();
+        // The flush is complete once the output wasn't filled up.
+        if status == Status::StreamEnd
+            || (!last && consumed && deflated.len() < deflated.capacity())
+        {
+            return deflated;
+        }
+    }
+}
+
+const ADLER_MODULUS: u32 = 65521;
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    // The sums can't overflow within 5552 bytes, see zlib.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= ADLER_MODULUS;
+        b %= ADLER_MODULUS;
+    }
+    (b << 16) | a
+}
+
+/// The Adler-32 of two joined pieces of data, from their checksums and the length of the second.
+fn adler32_combine(first: u32, second: u32, second_len: usize) -> u32 {
+    let len = (second_len % ADLER_MODULUS as usize) as u64;
+    let modulus = ADLER_MODULUS as u64;
+    let (a1, b1) = ((first & 0xffff) as u64, (first >> 16) as u64);
+    let (a2, b2) = ((second & 0xffff) as u64, (second >> 16) as u64);
+    let a = (a1 + a2 + modulus - 1) % modulus;
+    let b = (b1 + b2 + len * a1 + modulus - len) % modulus;
+    ((b << 16) | a) as u32
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::tasks::TaskPool;
+    use image::{ImageBuffer, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn test_image() -> DynamicImage {
+        // Odd sizes, so that the bands don't divide the rows evenly.
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(173, 91, |x, y| {
+            Rgba([(x * 7) as u8, (y * 3) as u8, (x ^ y) as u8, (x + y) as u8])
+        }))
+    }
+
+    #[test]
+    fn parallel_bands_match_serial_encoding() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        let image = test_image();
+        for bands in [1, 2, 7, 91, 200] {
+            let parallel = encode_png(&image, bands, true).unwrap();
+            let serial = encode_png(&image, bands, false).unwrap();
+            assert_eq!(parallel, serial, "{bands} bands");
+        }
+    }
+
+    #[test]
+    fn bands_decode_losslessly() {
+        let image = test_image();
+        for bands in [1, 4, 91] {
+            let png = encode_png(&image, bands, false).unwrap();
+            let decoded = image::load_from_memory(&png).unwrap();
+            assert_eq!(decoded.as_bytes(), image.as_bytes(), "{bands} bands");
+        }
+
+        let deep = DynamicImage::ImageRgba16(ImageBuffer::from_fn(9, 5, |x, y| {
+            Rgba([x as u16 * 7000, y as u16 * 300, 65535, 1])
+        }));
+        let decoded = image::load_from_memory(&encode_png(&deep, 3, false).unwrap()).unwrap();
+        assert_eq!(decoded, deep);
+        assert!(encode_png(&DynamicImage::new_rgba32f(2, 2), 2, false).is_none());
+    }
+
+    #[test]
+    fn combines_adler32_checksums() {
+        let data: Vec<u8> = (0..20_000).map(|i| (i * 31 % 251) as u8).collect();
+        let (first, second) = data.split_at(7919);
+        assert_eq!(
+            adler32_combine(adler32(first), adler32(second), second.len()),
+            adler32(&data)
+        );
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
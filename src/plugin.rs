@@ -1,47 +1,426 @@
-use crate::node::{ImageExportNode, NODE_NAME};
+use crate::adaptive::adapt_export_quality;
+use crate::camera::{export_marked_cameras, warn_shared_targets};
+use crate::capture::{
+    handle_capture_requests, handle_capture_resets, CaptureRequest, FirstCaptureSignal,
+    ResetCapture, SyncCaptures,
+};
+use crate::channel::{send_to_channels, ChannelExport};
+use crate::control::{add_export_control, GlobalExportControl};
+use crate::depth::{update_export_projections, ExportProjection};
+use crate::destination::{
+    copy_to_destinations, extract_gpu_destinations, resize_gpu_destinations, GpuDestinations,
+    ImageExportDestination,
+};
+use crate::disk::{
+    pace_disk_exports, resolve_export_extensions, save_buffer_to_disk, DiskExportRoot,
+    DiskExportStates, ImageExportSettings,
+};
+use crate::events::{
+    export_message_channel, receive_export_messages, update_achieved_fps, ExportFinished,
+    ExportStats, FrameExported, FrameVerificationFailed,
+};
+use crate::fifo::{write_to_fifos, FifoExport};
+use crate::frame_cache::{
+    extract_frame_cache, send_cached_frames, ExtractedFrameCache, FrameCacheExport,
+};
+use crate::layers::apply_export_render_layers;
+use crate::matte::{apply_alpha_mattes, AlphaMatte};
+use crate::mip_chain::{write_mip_chains, MipChainExport};
+#[cfg(feature = "mmap")]
+use crate::mmap::{write_to_mmap_files, MmapExport};
+use crate::node::{add_camera_export_nodes, add_image_export_node, CAMERA_NODE_NAME, NODE_NAME};
+use crate::session::update_recording_sessions;
+use crate::sidecar::{update_frame_sidecars, FrameSidecar};
+use crate::stereo::{compose_stereo_frames, StereoExport};
+use crate::supersample::apply_supersampling;
+use crate::switcher::switch_export_sources;
+use crate::timing::TimingLog;
+use crate::transparency::{apply_export_clear_colors, check_transparent_exports};
+#[cfg(feature = "upload")]
+use crate::upload::{upload_frames, UploadExport};
+use crate::viewports::{update_export_viewports, ExportViewports};
+#[cfg(feature = "websocket")]
+use crate::websocket::{send_to_websockets, WebSocketExport};
 use bevy::{
+    core::FrameCount,
+    core_pipeline::blit::{BlitPipeline, BlitPipelineKey},
     ecs::{
-        query::QueryItem,
-        system::{lifetimeless::SRes, SystemParamItem},
+        query::{Has, QueryItem},
+        system::{
+            lifetimeless::{SRes, SResMut},
+            SystemParamItem,
+        },
     },
     prelude::*,
     reflect::TypeUuid,
     render::{
-        camera::CameraUpdateSystem,
+        camera::{CameraUpdateSystem, RenderTarget},
         extract_component::{ExtractComponent, ExtractComponentPlugin},
         main_graph::node::CAMERA_DRIVER,
         render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
         render_graph::RenderGraph,
-        render_resource::{Buffer, BufferDescriptor, BufferUsages, Extent3d, MapMode},
-        renderer::RenderDevice,
-        Render, RenderApp, RenderSet,
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, CachedRenderPipelineId, Extent3d, MapMode,
+            PipelineCache, SpecializedRenderPipelines, Texture, TextureDescriptor,
+            TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
+        },
+        renderer::{RenderAdapterInfo, RenderDevice},
+        settings::WgpuFeatures,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
     },
+    transform::TransformSystem,
+    utils::{HashMap, HashSet},
 };
 use futures::channel::oneshot;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::{
+    borrow::Cow,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use wgpu::Maintain;
 
-#[derive(Clone, TypeUuid, Default, Reflect, Asset)]
+/// An asset selecting the [`Image`] an export reads its frames from.
+///
+/// Exports hold a `Handle<ImageExportSource>`, not the `Handle<Image>` of the image itself.
+/// Create the source from the image handle with [`ImageExportSource::new`] or `From` and add
+/// it to `Assets<ImageExportSource>`; [`image_handle`](Self::image_handle) returns the image.
+#[derive(Clone, TypeUuid, Reflect, Asset)]
 #[uuid = "d619b2f8-58cf-42f6-b7da-028c0595f7aa"]
-pub struct ImageExportSource(pub Handle<Image>);
+pub struct ImageExportSource {
+    attachments: Vec<Handle<Image>>,
+    attachment: usize,
+    keep_padding: bool,
+    node: String,
+    preview: Option<UVec2>,
+    capture_after_camera: Option<Entity>,
+    /// Bits of the additional [`BufferUsages`], which don't implement `Reflect`.
+    buffer_usages: u32,
+    /// Factor the image is enlarged by if it's the private target of a supersampled export.
+    supersample: u32,
+}
+
+impl ImageExportSource {
+    /// Creates a source reading from `image`.
+    pub fn new(image: Handle<Image>) -> Self {
+        Self::with_attachments(vec![image], 0)
+    }
+
+    /// Creates a source from the images a camera renders into with multiple color attachments
+    /// (MRT), one per attachment in attachment order. Only the image at index `attachment` is
+    /// copied and read back; [`set_attachment`](Self::set_attachment) switches to another one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attachment` is out of range.
+    pub fn with_attachments(attachments: Vec<Handle<Image>>, attachment: u32) -> Self {
+        let mut source = Self {
+            attachments,
+            attachment: 0,
+            keep_padding: false,
+            node: NODE_NAME.into(),
+            preview: None,
+            capture_after_camera: None,
+            buffer_usages: 0,
+            supersample: 1,
+        };
+        source.set_attachment(attachment);
+        source
+    }
+
+    /// Copy of the source exporting `image`, enlarged by `supersample`, instead of the selected
+    /// attachment.
+    pub(crate) fn supersampled(&self, image: Handle<Image>, supersample: u32) -> Self {
+        let mut source = self.clone();
+        source.attachments[source.attachment] = image;
+        source.supersample = supersample;
+        source
+    }
+
+    /// Index of the exported attachment.
+    pub fn attachment(&self) -> u32 {
+        self.attachment as u32
+    }
+
+    /// Selects which attachment is exported.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `attachment` is out of range.
+    pub fn set_attachment(&mut self, attachment: u32) {
+        let attachment = attachment as usize;
+        assert!(
+            attachment < self.attachments.len(),
+            "attachment {attachment} out of range for {} attachments",
+            self.attachments.len()
+        );
+        self.attachment = attachment;
+    }
+
+    /// Whether read back frames keep the row padding required by the GPU.
+    pub fn keep_padding(&self) -> bool {
+        self.keep_padding
+    }
+
+    /// Skips removing the row padding from read back frames, for exports that accept a row
+    /// stride, see [`ExportedFrames::padded_bytes_per_row`]. Other exports remove the padding
+    /// on their own.
+    pub fn set_keep_padding(&mut self, keep_padding: bool) {
+        self.keep_padding = keep_padding;
+    }
+
+    /// Name of the export node copying this source, [`NODE_NAME`] by default.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Assigns the source to a node added with
+    /// [`add_image_export_node_named`](crate::add_image_export_node_named).
+    pub fn set_node(&mut self, node: impl Into<String>) {
+        self.node = node.into();
+    }
+
+    /// Size of the preview read back instead of the full image, if any.
+    pub fn preview(&self) -> Option<UVec2> {
+        self.preview
+    }
+
+    /// Reads back a point sampled thumbnail of `size` instead of the full image, e.g. for a
+    /// cheap live preview of a headless app. The image is downsampled on the GPU, so only
+    /// the thumbnail is copied to the CPU.
+    pub fn set_preview(&mut self, size: Option<UVec2>) {
+        self.preview = size;
+    }
+
+    /// Camera after which the source is captured, if any.
+    pub fn capture_after_camera(&self) -> Option<Entity> {
+        self.capture_after_camera
+    }
+
+    /// Captures the image right after `camera` rendered into it, instead of after all cameras
+    /// did, e.g. to choose which of several cameras drawing into the same image the export
+    /// waits for. The camera's intermediate texture is copied, which includes the passes of
+    /// the cameras before it unless it clears the image, and must have the image's format.
+    ///
+    /// The source is copied by the [`CAMERA_NODE_NAME`](crate::CAMERA_NODE_NAME) node then,
+    /// regardless of [`node`](Self::node).
+    pub fn set_capture_after_camera(&mut self, camera: Option<Entity>) {
+        self.capture_after_camera = camera;
+    }
+
+    /// Usages of the buffer the source is read back into, always including
+    /// [`READBACK_BUFFER_USAGES`].
+    pub fn buffer_usages(&self) -> BufferUsages {
+        BufferUsages::from_bits_truncate(self.buffer_usages) | READBACK_BUFFER_USAGES
+    }
+
+    /// Adds `usages` to the buffer the source is read back into, e.g. for interop with
+    /// external APIs mapping the buffer. [`READBACK_BUFFER_USAGES`] are kept regardless.
+    ///
+    /// Mappable buffers can only have other usages if the device supports
+    /// `MAPPABLE_PRIMARY_BUFFERS`; otherwise the additional usages are dropped with an error.
+    pub fn set_buffer_usages(&mut self, usages: BufferUsages) {
+        self.buffer_usages = usages.bits();
+    }
+
+    /// The image that is exported.
+    pub fn image_handle(&self) -> &Handle<Image> {
+        &self.attachments[self.attachment]
+    }
+
+    /// Creates a source reading from the image a camera renders into.
+    ///
+    /// Returns `None` if the camera does not target an image. Only what the camera draws
+    /// (e.g. the entities on its `RenderLayers`) ends up in the export, as long as no other
+    /// camera renders into the same image.
+    pub fn from_camera(camera: &Camera) -> Option<Self> {
+        match &camera.target {
+            RenderTarget::Image(handle) => Some(handle.clone().into()),
+            _ => None,
+        }
+    }
+
+    /// Creates an image of `size` for a camera to render into and export from, with the usages
+    /// the export needs, e.g. `Rgba16Float` for HDR capture.
+    ///
+    /// Fails if `format` can't be rendered to or read back as a whole, such as depth-stencil,
+    /// multi-planar or block compressed formats.
+    pub fn target_image(size: UVec2, format: TextureFormat) -> Result<Image, ExportFormatError> {
+        let error = |reason| Err(ExportFormatError { format, reason });
+        if format.is_compressed() {
+            return error("block compressed formats can't be rendered to");
+        }
+        if format.block_size(None).is_none() {
+            return error("depth-stencil and multi-planar formats can't be copied as a whole");
+        }
+        let usage = TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC
+            | TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING;
+        if !format
+            .guaranteed_format_features(WgpuFeatures::empty())
+            .allowed_usages
+            .contains(usage)
+        {
+            return error("the format can't be rendered to and sampled on every device");
+        }
+
+        let size = Extent3d {
+            width: size.x.max(1),
+            height: size.y.max(1),
+            depth_or_array_layers: 1,
+        };
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: None,
+                size,
+                dimension: TextureDimension::D2,
+                format,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        image.resize(size);
+        Ok(image)
+    }
+}
+
+/// Returned by [`ImageExportSource::target_image`] for formats the export can't handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExportFormatError {
+    pub format: TextureFormat,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for ExportFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot export {:?}: {}", self.format, self.reason)
+    }
+}
+
+impl std::error::Error for ExportFormatError {}
+
+impl Default for ImageExportSource {
+    fn default() -> Self {
+        Self::new(Handle::default())
+    }
+}
 
 impl From<Handle<Image>> for ImageExportSource {
     fn from(value: Handle<Image>) -> Self {
-        Self(value)
+        Self::new(value)
+    }
+}
+
+impl From<&Handle<Image>> for ImageExportSource {
+    fn from(value: &Handle<Image>) -> Self {
+        Self::new(value.clone())
     }
 }
 
 pub struct GpuImageExportSource {
     pub buffer: Buffer,
+    /// The [`FrameCount`](bevy::core::FrameCount) of the frame the export node last copied
+    /// the source into `buffer`.
+    pub copied_frame: AtomicU32,
+    /// The [`FrameCount`](bevy::core::FrameCount) of the frame the export node last skipped
+    /// because the preview pipeline was still compiling.
+    pub skipped_frame: AtomicU32,
     pub source_handle: Handle<Image>,
     pub source_size: Extent3d,
+    /// Format of the copied texture, which frames are read back as.
+    pub format: TextureFormat,
+    /// Single-sampled texture a multisampled source is resolved into before it is copied.
+    pub resolve_target: Option<(Texture, TextureView)>,
     pub bytes_per_row: u32,
     pub padded_bytes_per_row: u32,
+    pub keep_padding: bool,
+    /// Name of the export node copying the source.
+    pub node: String,
+    /// Camera after which the source is copied, instead of by the node named `node`.
+    pub capture_after_camera: Option<Entity>,
+    /// Target the source is downsampled into before it's copied, if it's exported as a
+    /// preview. `source_size` is the size of the preview then.
+    pub preview: Option<GpuExportPreview>,
+    /// Factor the frames are enlarged by if the source is the private target of a supersampled
+    /// export, 1 otherwise.
+    pub supersample: u32,
+}
+
+/// Downsampled copy of a source exported as a preview.
+pub struct GpuExportPreview {
+    pub texture: Texture,
+    pub view: TextureView,
+    pub pipeline: CachedRenderPipelineId,
+}
+
+/// Usages every readback buffer is created with.
+pub const READBACK_BUFFER_USAGES: BufferUsages =
+    BufferUsages::COPY_DST.union(BufferUsages::MAP_READ);
+
+/// Sources whose readback buffer can't be created. `prepare_asset` retries them every frame,
+/// so the error is only logged once.
+#[derive(Resource, Default)]
+pub struct RejectedExportSources(HashSet<AssetId<Image>>);
+
+/// Computes the unpadded and padded row sizes and the total size of the buffer an image is
+/// read back into, using `u64` math so that huge textures can't overflow.
+///
+/// Rows are rows of texel blocks, so a block compressed texture has `ceil(height / block
+/// height)` rows of `ceil(width / block width)` blocks.
+pub(crate) fn readback_layout(
+    size: Extent3d,
+    format: TextureFormat,
+    max_buffer_size: u64,
+) -> Result<(u32, u32, u64), String> {
+    let block_size = format
+        .block_size(None)
+        .ok_or_else(|| format!("{format:?} textures can't be copied to a buffer"))?;
+    let (block_width, block_height) = format.block_dimensions();
+    let bytes_per_row = (size.width as u64).div_ceil(block_width as u64) * block_size as u64;
+    let padded_bytes_per_row =
+        RenderDevice::align_copy_bytes_per_row(bytes_per_row as usize) as u64;
+    let buffer_size = padded_bytes_per_row * (size.height as u64).div_ceil(block_height as u64);
+
+    if padded_bytes_per_row > u32::MAX as u64 {
+        return Err(format!(
+            "a row of a {}x{} {format:?} texture needs {padded_bytes_per_row} bytes, exceeding \
+             the {} bytes a copy can address",
+            size.width,
+            size.height,
+            u32::MAX
+        ));
+    }
+    if buffer_size > max_buffer_size {
+        return Err(format!(
+            "the readback buffer for a {}x{} {format:?} texture needs {buffer_size} bytes, \
+             exceeding the device limit of {max_buffer_size} bytes",
+            size.width, size.height
+        ));
+    }
+
+    Ok((
+        bytes_per_row as u32,
+        padded_bytes_per_row as u32,
+        buffer_size,
+    ))
 }
 
 impl RenderAsset for ImageExportSource {
     type ExtractedAsset = Self;
     type PreparedAsset = GpuImageExportSource;
-    type Param = (SRes<RenderDevice>, SRes<RenderAssets<Image>>);
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<RenderAssets<Image>>,
+        SResMut<RejectedExportSources>,
+        SRes<PipelineCache>,
+        Option<SRes<BlitPipeline>>,
+        Option<SResMut<SpecializedRenderPipelines<BlitPipeline>>>,
+    );
 
     fn extract_asset(&self) -> Self::ExtractedAsset {
         self.clone()
@@ -49,148 +428,955 @@ impl RenderAsset for ImageExportSource {
 
     fn prepare_asset(
         extracted_asset: Self::ExtractedAsset,
-        (device, images): &mut SystemParamItem<Self::Param>,
+        (device, images, rejected, pipeline_cache, blit_pipeline, blit_pipelines): &mut SystemParamItem<
+            Self::Param,
+        >,
     ) -> Result<Self::PreparedAsset, PrepareAssetError<Self::ExtractedAsset>> {
-        let gpu_image = images.get(&extracted_asset.0).unwrap();
+        let Some(gpu_image) = images.get(extracted_asset.image_handle()) else {
+            return Err(PrepareAssetError::RetryNextUpdate(extracted_asset));
+        };
+
+        let preview = extracted_asset.preview.and_then(|size| {
+            let (Some(blit_pipeline), Some(blit_pipelines)) = (blit_pipeline, blit_pipelines)
+            else {
+                warn!(
+                    "image export previews need the core pipeline plugin, exporting the full image"
+                );
+                return None;
+            };
+            let size = Extent3d {
+                width: size.x.max(1),
+                height: size.y.max(1),
+                depth_or_array_layers: 1,
+            };
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("Image Export Preview"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: gpu_image.texture_format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            let pipeline = blit_pipelines.specialize(
+                pipeline_cache,
+                blit_pipeline,
+                BlitPipelineKey {
+                    texture_format: gpu_image.texture_format,
+                    blend_state: None,
+                    samples: 1,
+                },
+            );
+            Some(GpuExportPreview {
+                texture,
+                view,
+                pipeline,
+            })
+        });
+
+        let source_size = match &preview {
+            Some(preview) => preview.texture.size(),
+            None => gpu_image.texture.size(),
+        };
+        let (bytes_per_row, padded_bytes_per_row, buffer_size) = match readback_layout(
+            source_size,
+            gpu_image.texture_format,
+            device.limits().max_buffer_size,
+        ) {
+            Ok(layout) => layout,
+            Err(e) => {
+                let id = extracted_asset.image_handle().id();
+                if rejected.0.insert(id) {
+                    error!("cannot export image {id:?}: {e}");
+                }
+                return Err(PrepareAssetError::RetryNextUpdate(extracted_asset));
+            }
+        };
 
-        let size = gpu_image.texture.size();
-        let format = &gpu_image.texture_format;
-        let bytes_per_row =
-            (size.width / format.block_dimensions().0) * format.block_size(None).unwrap();
-        let padded_bytes_per_row =
-            RenderDevice::align_copy_bytes_per_row(bytes_per_row as usize) as u32;
+        // The resolve target matches the multisampled image, a preview is blitted from it.
+        let resolve_target = (gpu_image.texture.sample_count() > 1).then(|| {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("Image Export Resolve Target"),
+                size: gpu_image.texture.size(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: gpu_image.texture_format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        });
 
-        let source_size = gpu_image.texture.size();
+        let mut usage = extracted_asset.buffer_usages();
+        if usage != READBACK_BUFFER_USAGES
+            && !device
+                .features()
+                .contains(WgpuFeatures::MAPPABLE_PRIMARY_BUFFERS)
+        {
+            error!(
+                "readback buffer usages {usage:?} of image source {:?} need the MAPPABLE_PRIMARY_BUFFERS feature, using {READBACK_BUFFER_USAGES:?}",
+                extracted_asset.image_handle().id()
+            );
+            usage = READBACK_BUFFER_USAGES;
+        }
 
         Ok(GpuImageExportSource {
             buffer: device.create_buffer(&BufferDescriptor {
                 label: Some("Image Export Buffer"),
-                size: (source_size.height * padded_bytes_per_row) as u64,
-                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                size: buffer_size,
+                usage,
                 mapped_at_creation: false,
             }),
-            source_handle: extracted_asset.0.clone(),
+            copied_frame: AtomicU32::new(u32::MAX),
+            skipped_frame: AtomicU32::new(u32::MAX),
+            source_handle: extracted_asset.image_handle().clone(),
             source_size,
+            format: gpu_image.texture_format,
+            resolve_target,
             bytes_per_row,
             padded_bytes_per_row,
+            keep_padding: extracted_asset.keep_padding,
+            node: extracted_asset.node,
+            capture_after_camera: extracted_asset.capture_after_camera,
+            preview,
+            supersample: extracted_asset.supersample,
         })
     }
 }
 
-#[derive(Component, Clone, Copy, Default)]
-pub struct ImageExport;
+#[derive(Component, Clone, Copy)]
+pub struct ImageExport {
+    /// Whether the export keeps its source alive. Enabled by default, which upgrades a weak
+    /// source handle on the entity to a strong one while the source is loaded. If disabled,
+    /// the handle on the entity is downgraded to a weak one, so the export stops as soon as all
+    /// other strong handles to the source are dropped.
+    pub retain_source: bool,
+    /// Total number of [`CaptureRequest`](crate::CaptureRequest)s received.
+    pub(crate) requested_captures: u32,
+    /// Total number of [`ResetCapture`](crate::ResetCapture)s received.
+    pub(crate) resets: u32,
+    /// Set by a [`RecordingSession`](crate::RecordingSession) to skip frames.
+    pub(crate) paused: bool,
+}
+
+impl Default for ImageExport {
+    fn default() -> Self {
+        Self {
+            retain_source: true,
+            requested_captures: 0,
+            resets: 0,
+            paused: false,
+        }
+    }
+}
+
+impl ImageExport {
+    /// An export that doesn't keep its source alive, see
+    /// [`retain_source`](Self::retain_source).
+    pub fn with_weak_source() -> Self {
+        Self {
+            retain_source: false,
+            ..default()
+        }
+    }
+}
 
 impl ExtractComponent for ImageExport {
-    type Query = (&'static ImageExport, &'static Handle<ImageExportSource>);
+    type Query = (
+        &'static ImageExport,
+        &'static Handle<ImageExportSource>,
+        &'static ImageExportSettings,
+    );
     type Filter = ();
-    type Out = (ImageExport, Handle<ImageExportSource>);
+    type Out = (ImageExport, Handle<ImageExportSource>, ImageExportSettings);
 
-    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
-        //dbg!("extract");
-        Some((*this, source_handle.clone_weak()))
+    fn extract_component(
+        (this, source_handle, settings): QueryItem<'_, Self::Query>,
+    ) -> Option<Self::Out> {
+        trace!(
+            "extracting image export for source {:?}",
+            source_handle.id()
+        );
+        Some((*this, source_handle.clone(), settings.clone()))
     }
 }
 
 #[derive(Bundle, Default)]
 pub struct ImageExportBundle {
+    /// Made strong or weak depending on [`ImageExport::retain_source`]. A strong handle keeps the
+    /// source, the image it reads from and its readback buffer in memory for as long as the
+    /// component exists, even if all other handles are dropped.
     pub source: Handle<ImageExportSource>,
     pub export: ImageExport,
+    pub settings: ImageExportSettings,
+    pub stats: ExportStats,
 }
 
-fn save_buffer_to_disk(
-    export_bundles: Query<Ref<Handle<ImageExportSource>>>,
-    sources: Res<RenderAssets<ImageExportSource>>,
-    render_device: Res<RenderDevice>,
+/// Makes the source handles of exports match their [`ImageExport::retain_source`], downgrading
+/// or upgrading them once. Weak handles that can't be upgraded, because the source isn't loaded
+/// or the entity has only other sinks, get a warning, as their exports silently stop as soon as
+/// all strong handles are dropped elsewhere.
+#[allow(clippy::type_complexity)]
+fn apply_source_retention(
+    mut handles: Query<
+        (Entity, Option<&ImageExport>, &mut Handle<ImageExportSource>),
+        Or<(Changed<ImageExport>, Changed<Handle<ImageExportSource>>)>,
+    >,
+    mut sources: ResMut<Assets<ImageExportSource>>,
 ) {
-    let sources = sources.into_inner();
-    let render_device = render_device.into_inner();
-    for source_handle in &export_bundles {
-        if let Some(img) = get_image(
-            source_handle.clone(),
-            sources,
-            render_device,
-        ) {
-            if let Ok(dy) = img.try_into_dynamic() {
-                dbg!("saving");
-                dy.save("test.png").ok();
+    for (entity, export, mut handle) in &mut handles {
+        let retain_source = export.is_none_or(|export| export.retain_source);
+        match (&*handle, retain_source) {
+            (Handle::Strong(_), false) => {
+                debug!("export {entity:?} doesn't retain its source, downgrading its handle");
+                *handle = handle.clone_weak();
             }
+            (&Handle::Weak(id), true) => {
+                match export.and_then(|_| sources.get_strong_handle(id)) {
+                    Some(strong) => *handle = strong,
+                    None => warn!("export {entity:?} holds a weak handle to image export source {id:?}, it stops once all strong handles are dropped"),
+                }
+            }
+            _ => {}
         }
     }
 }
 
+/// Checks that an export source and the image it reads from are still loaded.
+///
+/// Either asset may be unloaded while the export component lives on, e.g. when the component
+/// only holds a weak handle. A warning is logged once per source when that happens; the
+/// source's GPU buffer is released together with the asset.
+pub(crate) fn source_is_present(
+    id: AssetId<ImageExportSource>,
+    sources: &RenderAssets<ImageExportSource>,
+    images: &RenderAssets<Image>,
+    missing_sources: &mut HashSet<AssetId<ImageExportSource>>,
+) -> bool {
+    let present = sources
+        .get(id)
+        .is_some_and(|gpu_source| images.get(&gpu_source.source_handle).is_some());
+
+    if present {
+        if missing_sources.remove(&id) {
+            info!("image export source {id:?} is available again");
+        }
+    } else if missing_sources.insert(id) {
+        warn!("image export source {id:?} (or the image it reads from) is no longer loaded, skipping export");
+    }
+
+    present
+}
+
+/// Added to the render world entities of every sink that uses each exported frame, e.g. a
+/// [`FifoExport`] or an NDI stream, so that [`read_back_frames`] doesn't skip the frames an
+/// [`ImageExport`] on the same entity wouldn't write to disk.
+#[derive(Component, Clone, Copy, Default)]
+pub struct StreamingSink;
+
+/// Describes the adapter rendering and reading back the exports along with the other adapters
+/// of the system. Buffers and textures created on different devices, e.g. by another plugin on
+/// a multi-GPU system, fail to map without a hint at the cause, so this is logged once when a
+/// readback fails.
+#[derive(Resource)]
+pub(crate) struct AdapterDiagnostics {
+    description: String,
+    logged: AtomicBool,
+}
+
+impl FromWorld for AdapterDiagnostics {
+    fn from_world(world: &mut World) -> Self {
+        let describe = |info: &wgpu::AdapterInfo| {
+            format!(
+                "\"{}\" ({:?}, {:?}, vendor {:#06x}, device {:#06x}, driver {} {})",
+                info.name,
+                info.device_type,
+                info.backend,
+                info.vendor,
+                info.device,
+                info.driver,
+                info.driver_info
+            )
+        };
+        let Some(adapter_info) = world.get_resource::<RenderAdapterInfo>() else {
+            return Self {
+                description: "no render adapter was initialized".into(),
+                logged: default(),
+            };
+        };
+        let mut description = format!("exports use adapter {}", describe(adapter_info));
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(instance) = world.get_resource::<bevy::render::renderer::RenderInstance>() {
+            for adapter in instance.enumerate_adapters(wgpu::Backends::all()) {
+                let info = adapter.get_info();
+                if info != adapter_info.0 {
+                    description.push_str(&format!(", also available: {}", describe(&info)));
+                }
+            }
+        }
+        Self {
+            description,
+            logged: default(),
+        }
+    }
+}
+
+impl AdapterDiagnostics {
+    /// Logs the adapters after the first failed readback.
+    pub(crate) fn log_once(&self) {
+        if !self.logged.swap(true, Ordering::Relaxed) {
+            error!(
+                "{}; check that the exported images are rendered on the same device",
+                self.description
+            );
+        }
+    }
+}
+
+/// Bounds of the sleep between polls of a pending readback, see [`get_image`].
+const MIN_POLL_INTERVAL: Duration = Duration::from_micros(50);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Reads the frame copied by the export node back from the GPU into `buffer`, which is taken
+/// as the data of the returned image.
+///
+/// The buffer's allocation is reused, so passing the data of the previous frame avoids
+/// allocating as long as the size of the source doesn't change.
+///
+/// Returns `None` with a warning if the buffer doesn't hold the copy of the current frame, e.g.
+/// because the caller was scheduled before the render graph ran, instead of silently returning
+/// a stale frame.
 pub(crate) fn get_image(
-    source_handle: Handle<ImageExportSource>,
+    id: AssetId<ImageExportSource>,
     sources: &RenderAssets<ImageExportSource>,
     render_device: &RenderDevice,
+    frame_count: u32,
+    timeout: Duration,
+    buffer: &mut Vec<u8>,
+    diagnostics: &AdapterDiagnostics,
 ) -> Option<Image> {
-    //dbg!(&source_handle);
-    if let Some(gpu_source) = sources.get(source_handle.id()) {
-        let mut image_bytes = {
+    if let Some(gpu_source) = sources.get(id) {
+        let copied_frame = gpu_source.copied_frame.load(Ordering::Acquire);
+        if gpu_source.skipped_frame.load(Ordering::Acquire) == frame_count {
+            debug!("the preview pipeline of image source {id:?} is still compiling, skipping frame {frame_count}");
+            return None;
+        }
+        if copied_frame != frame_count {
+            warn!(
+                "readback buffer of image source {id:?} holds frame {copied_frame} instead of frame {frame_count}, skipping stale capture"
+            );
+            return None;
+        }
+
+        {
             let slice = gpu_source.buffer.slice(..);
 
             {
-                let (mapping_tx, mapping_rx) = oneshot::channel();
+                let (mapping_tx, mut mapping_rx) = oneshot::channel();
 
                 render_device.map_buffer(&slice, MapMode::Read, move |res| {
-                    mapping_tx.send(res).unwrap();
+                    // The receiver is gone if the readback timed out.
+                    mapping_tx.send(res).ok();
                 });
 
-                render_device.poll(Maintain::Wait);
-                futures_lite::future::block_on(mapping_rx).unwrap().unwrap();
+                // Polls instead of waiting, so that a hung or lost device can't block the
+                // render thread forever. Sleeps between polls, backing off up to
+                // `MAX_POLL_INTERVAL`, instead of spinning a core while the GPU is busy.
+                let deadline = Instant::now() + timeout;
+                let mut interval = MIN_POLL_INTERVAL;
+                let result = loop {
+                    render_device.poll(Maintain::Poll);
+                    match mapping_rx.try_recv() {
+                        Ok(Some(result)) => break result.map_err(|e| e.to_string()),
+                        Ok(None) if Instant::now() < deadline => {
+                            std::thread::sleep(interval);
+                            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+                        }
+                        Ok(None) => break Err(format!("timed out after {timeout:?}")),
+                        Err(_) => break Err("mapping was cancelled".into()),
+                    }
+                };
+                if let Err(e) = result {
+                    error!("failed to read back image source {id:?}: {e}");
+                    diagnostics.log_once();
+                    // Cancels the pending mapping, so that the buffer can be mapped again.
+                    gpu_source.buffer.unmap();
+                    return None;
+                }
             }
 
-            slice.get_mapped_range().to_vec()
-        };
+            copy_readback(
+                &slice.get_mapped_range(),
+                gpu_source.bytes_per_row,
+                gpu_source.padded_bytes_per_row,
+                gpu_source.keep_padding,
+                buffer,
+            );
+        }
 
         gpu_source.buffer.unmap();
 
-        let bytes_per_row = gpu_source.bytes_per_row as usize;
-        let padded_bytes_per_row = gpu_source.padded_bytes_per_row as usize;
-        let source_size = gpu_source.source_size;
+        return Some(readback_image(
+            std::mem::take(buffer),
+            gpu_source.source_size,
+            gpu_source.format,
+        ));
+    }
+    None
+}
 
-        if bytes_per_row != padded_bytes_per_row {
-            let mut unpadded_bytes =
-                Vec::<u8>::with_capacity(source_size.height as usize * bytes_per_row);
+/// Copies a mapped readback buffer into `buffer`, removing the row padding unless
+/// `keep_padding` is set. Rows without padding are copied in one go.
+///
+/// Doesn't allocate as long as `buffer` has the capacity of the previous frame of the same
+/// size.
+fn copy_readback(
+    mapped: &[u8],
+    bytes_per_row: u32,
+    padded_bytes_per_row: u32,
+    keep_padding: bool,
+    buffer: &mut Vec<u8>,
+) {
+    buffer.clear();
+    if keep_padding || bytes_per_row == padded_bytes_per_row {
+        buffer.extend_from_slice(mapped);
+    } else {
+        unpad_into(mapped, bytes_per_row, padded_bytes_per_row, buffer);
+    }
+}
 
-            for padded_row in image_bytes.chunks(padded_bytes_per_row) {
-                unpadded_bytes.extend_from_slice(&padded_row[..bytes_per_row]);
-            }
+/// The image of a frame read back into `data`.
+fn readback_image(data: Vec<u8>, size: Extent3d, format: TextureFormat) -> Image {
+    Image {
+        data,
+        texture_descriptor: wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            format,
+            dimension: wgpu::TextureDimension::D2,
+            label: None,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        sampler: bevy::render::texture::ImageSampler::Default,
+        texture_view_descriptor: None,
+    }
+}
+
+/// Removes the padding from rows of `bytes_per_row` bytes, stored every `padded_bytes_per_row`.
+/// Rows of block compressed textures are rows of blocks.
+pub(crate) fn unpad(data: &[u8], bytes_per_row: u32, padded_bytes_per_row: u32) -> Vec<u8> {
+    let mut unpadded = Vec::new();
+    unpad_into(data, bytes_per_row, padded_bytes_per_row, &mut unpadded);
+    unpadded
+}
+
+/// Like [`unpad`], but appends the rows to `unpadded`.
+fn unpad_into(data: &[u8], bytes_per_row: u32, padded_bytes_per_row: u32, unpadded: &mut Vec<u8>) {
+    let (bytes_per_row, padded_bytes_per_row) =
+        (bytes_per_row as usize, padded_bytes_per_row as usize);
+    unpadded.reserve(data.len() / padded_bytes_per_row * bytes_per_row);
+    for padded_row in data.chunks(padded_bytes_per_row) {
+        // A truncated last row is copied as far as it goes instead of panicking.
+        unpadded.extend_from_slice(&padded_row[..bytes_per_row.min(padded_row.len())]);
+    }
+}
 
-            image_bytes = unpadded_bytes;
+struct ExportedFrame {
+    /// Shared with the sinks that hand the frame to other threads, see
+    /// [`ExportedFrames::get_export_shared`].
+    image: Arc<Image>,
+    /// Unpadded and padded row size, if the image data is still padded.
+    padding: Option<(u32, u32)>,
+    readback_time: Duration,
+}
+
+/// Frames read back from the GPU this frame, keyed by their export source.
+///
+/// Systems in [`ImageExportRenderSystems::PostReadback`] may modify the frames, e.g. to add a
+/// watermark, before the exports consume them. Frames of sources that
+/// [keep their padding](ImageExportSource::set_keep_padding) have padded rows.
+///
+/// The data of the frames is reused for the next readback of the same source, so frames are
+/// only allocated when a source is read back for the first time, changes its size, or a sink
+/// still holds the previous frame, e.g. a writer thread falling behind.
+///
+/// Exports that combine their source with others, e.g. a [`StereoExport`], get a frame of their
+/// own that only the sinks on their entity see, see [`get_export`](Self::get_export).
+#[derive(Resource, Default)]
+pub struct ExportedFrames {
+    frames: HashMap<AssetId<ImageExportSource>, ExportedFrame>,
+    /// Frames composed for the export on an entity, replacing the frame of its source for the
+    /// sinks on that entity. `None` drops the frame for them.
+    composed: HashMap<Entity, Option<Arc<Image>>>,
+    /// Data of the previous frames, reused by the next readback of their source.
+    buffers: HashMap<AssetId<ImageExportSource>, Vec<u8>>,
+}
+
+impl ExportedFrames {
+    pub fn get(&self, id: impl Into<AssetId<ImageExportSource>>) -> Option<&Image> {
+        self.frames.get(&id.into()).map(|frame| &*frame.image)
+    }
+
+    /// The frame exported by the sinks on `entity` reading from source `id`: the frame
+    /// composed for that export, if any, or else the frame of the source.
+    pub fn get_export(
+        &self,
+        entity: Entity,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<&Image> {
+        match self.composed.get(&entity) {
+            Some(composed) => composed.as_deref(),
+            None => self.get(id),
         }
-        //dbg!(image_bytes.len());
+    }
 
-        let img = Image {
-            data: image_bytes,
-            texture_descriptor: wgpu::TextureDescriptor {
-                size: wgpu::Extent3d {
-                    width: source_size.width,
-                    height: source_size.height,
-                    depth_or_array_layers: 1,
-                },
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                dimension: wgpu::TextureDimension::D2,
-                label: None,
-                mip_level_count: 1,
-                sample_count: 1,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
+    /// Like [`get_export`](Self::get_export), but removes the row padding if there is any.
+    pub fn get_export_unpadded(
+        &self,
+        entity: Entity,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<Cow<'_, Image>> {
+        match self.composed.get(&entity) {
+            Some(composed) => composed.as_deref().map(Cow::Borrowed),
+            None => self.get_unpadded(id),
+        }
+    }
+
+    /// Like [`get_export_unpadded`](Self::get_export_unpadded), but shares the frame instead
+    /// of borrowing it, for sinks handing it to another thread. Only frames with padded rows
+    /// are copied.
+    pub fn get_export_shared(
+        &self,
+        entity: Entity,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<Arc<Image>> {
+        if let Some(composed) = self.composed.get(&entity) {
+            return composed.clone();
+        }
+        let frame = self.frames.get(&id.into())?;
+        Some(match frame.padding {
+            Some(padding) => Arc::new(unpadded_image(&frame.image, padding)),
+            None => frame.image.clone(),
+        })
+    }
+
+    /// Like [`padded_bytes_per_row`](Self::padded_bytes_per_row) for the frame returned by
+    /// [`get_export`](Self::get_export). Composed frames are never padded.
+    pub fn export_padded_bytes_per_row(
+        &self,
+        entity: Entity,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<u32> {
+        match self.composed.get(&entity) {
+            Some(_) => None,
+            None => self.padded_bytes_per_row(id),
+        }
+    }
+
+    /// Sets the unpadded frame exported by the sinks on `entity` instead of the frame of its
+    /// source, e.g. one composed from multiple sources. `None` drops the frame for them.
+    pub(crate) fn compose(&mut self, entity: Entity, image: Option<Image>) {
+        self.composed.insert(entity, image.map(Arc::new));
+    }
+
+    pub fn get_mut(&mut self, id: impl Into<AssetId<ImageExportSource>>) -> Option<&mut Image> {
+        self.frames
+            .get_mut(&id.into())
+            .map(|frame| Arc::make_mut(&mut frame.image))
+    }
+
+    /// How long reading the frame of a source back from the GPU took.
+    pub(crate) fn readback_time(
+        &self,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<Duration> {
+        self.frames.get(&id.into()).map(|frame| frame.readback_time)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (AssetId<ImageExportSource>, &mut Image)> {
+        self.frames
+            .iter_mut()
+            .map(|(id, frame)| (*id, Arc::make_mut(&mut frame.image)))
+    }
+
+    /// Drops the frames of the previous readback, keeping their data for the next readback of
+    /// their source unless a sink still holds them.
+    fn recycle(&mut self) {
+        for (id, frame) in self.frames.drain() {
+            if let Ok(image) = Arc::try_unwrap(frame.image) {
+                self.buffers.insert(id, image.data);
+            }
+        }
+        self.composed.clear();
+    }
+
+    /// The data of the previous frame of `id`, to read the next one back into.
+    fn buffer(&mut self, id: AssetId<ImageExportSource>) -> &mut Vec<u8> {
+        self.buffers.entry(id).or_default()
+    }
+
+    fn insert(
+        &mut self,
+        id: AssetId<ImageExportSource>,
+        image: Image,
+        padding: Option<(u32, u32)>,
+        readback_time: Duration,
+    ) {
+        self.frames.insert(
+            id,
+            ExportedFrame {
+                image: Arc::new(image),
+                padding,
+                readback_time,
             },
-            sampler: bevy::render::texture::ImageSampler::Default,
-            texture_view_descriptor: None,
+        );
+    }
+
+    /// Size of a row of the frame in bytes including padding, if its rows are padded.
+    pub fn padded_bytes_per_row(&self, id: impl Into<AssetId<ImageExportSource>>) -> Option<u32> {
+        self.frames
+            .get(&id.into())
+            .and_then(|frame| frame.padding)
+            .map(|(_, padded_bytes_per_row)| padded_bytes_per_row)
+    }
+
+    /// Like [`get`](Self::get), but removes the row padding if there is any.
+    pub fn get_unpadded(
+        &self,
+        id: impl Into<AssetId<ImageExportSource>>,
+    ) -> Option<Cow<'_, Image>> {
+        let frame = self.frames.get(&id.into())?;
+        Some(match frame.padding {
+            Some(padding) => Cow::Owned(unpadded_image(&frame.image, padding)),
+            None => Cow::Borrowed(&frame.image),
+        })
+    }
+}
+
+/// Copy of a frame with padded rows, without the padding.
+fn unpadded_image(image: &Image, (bytes_per_row, padded_bytes_per_row): (u32, u32)) -> Image {
+    Image {
+        data: unpad(&image.data, bytes_per_row, padded_bytes_per_row),
+        texture_descriptor: image.texture_descriptor.clone(),
+        sampler: image.sampler.clone(),
+        texture_view_descriptor: image.texture_view_descriptor.clone(),
+    }
+}
+
+/// Render world system sets of the image export, in the order they run between
+/// [`RenderSet::Render`] and [`RenderSet::Cleanup`].
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum ImageExportRenderSystems {
+    /// Reads the frames back into [`ExportedFrames`].
+    Readback,
+    /// Runs user systems modifying or analyzing [`ExportedFrames`].
+    PostReadback,
+    /// Writes, sends or copies the frames.
+    Export,
+}
+
+/// Reads back the sources of all exports, skipping disk exports that don't write this frame or
+/// aren't written at all because the disk sink is disabled, unless a [`StreamingSink`] on the
+/// same entity uses the frame.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn read_back_frames(
+    exports: Query<(
+        Entity,
+        &Handle<ImageExportSource>,
+        Option<(&ImageExport, &ImageExportSettings)>,
+        Has<StreamingSink>,
+        Option<&AlphaMatte>,
+        Option<&StereoExport>,
+    )>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    render_device: Res<RenderDevice>,
+    frame_count: Res<FrameCount>,
+    states: Option<Res<DiskExportStates>>,
+    control: Res<GlobalExportControl>,
+    first_capture: Option<Res<FirstCaptureSignal>>,
+    readback_timeout: Res<ReadbackTimeout>,
+    sync_captures: Res<SyncCaptures>,
+    cpu_fallback: Res<CpuFallbackImages>,
+    diagnostics: Res<AdapterDiagnostics>,
+    frames: ResMut<ExportedFrames>,
+) {
+    let frames = frames.into_inner();
+    frames.recycle();
+    if control.paused {
+        return;
+    }
+    let mut wanted = Vec::new();
+    for (entity, source_handle, disk_export, streaming, matte, stereo) in &exports {
+        if let (Some((export, settings)), false) = (disk_export, streaming) {
+            if !states
+                .as_ref()
+                .is_some_and(|states| states.wants_frame(entity, export, settings, frame_count.0))
+            {
+                continue;
+            }
+        }
+        wanted.push(source_handle.id());
+        wanted.extend(matte.map(|matte| matte.0.id()));
+        wanted.extend(stereo.map(|stereo| stereo.right_eye.id()));
+    }
+    wanted.extend(sync_captures.0.iter().copied());
+
+    for id in wanted {
+        if frames.frames.contains_key(&id) {
+            continue;
+        }
+        if let Some((_, Some(image))) = cpu_fallback.0.get(&id) {
+            frames.insert(id, image.clone(), None, Duration::ZERO);
+            // Not a capture of what was rendered, so it doesn't complete
+            // `wait_for_first_capture`.
+            continue;
+        }
+        let readback_start = Instant::now();
+        if let Some(image) = get_image(
+            id,
+            &sources,
+            &render_device,
+            frame_count.0,
+            readback_timeout.0,
+            frames.buffer(id),
+            &diagnostics,
+        ) {
+            let padding = sources
+                .get(id)
+                .filter(|gpu_source| {
+                    gpu_source.keep_padding
+                        && gpu_source.bytes_per_row != gpu_source.padded_bytes_per_row
+                })
+                .map(|gpu_source| (gpu_source.bytes_per_row, gpu_source.padded_bytes_per_row));
+            frames.insert(id, image, padding, readback_start.elapsed());
+            if let Some(first_capture) = &first_capture {
+                first_capture.0.store(true, Ordering::Release);
+            }
+        }
+    }
+    frames.buffers.retain(|id, _| sources.get(*id).is_some());
+}
+
+/// CPU-side data of the source images whose export node is missing from the render graph,
+/// along with the image it was extracted from. It's exported instead of the empty readback
+/// buffer, unless the image can't be exported that way.
+#[derive(Resource, Default)]
+pub(crate) struct CpuFallbackImages(
+    HashMap<AssetId<ImageExportSource>, (AssetId<Image>, Option<Image>)>,
+);
+
+/// Whether no node that could copy `gpu_source` is in the render graph or its sub graphs, e.g.
+/// because a custom graph removed it.
+fn export_node_missing(graph: &RenderGraph, gpu_source: &GpuImageExportSource) -> bool {
+    let node = match gpu_source.capture_after_camera {
+        Some(_) => CAMERA_NODE_NAME.to_string(),
+        None => gpu_source.node.clone(),
+    };
+    graph.get_node_state(node.clone()).is_err()
+        && graph
+            .iter_sub_graphs()
+            .all(|(_, sub_graph)| sub_graph.get_node_state(node.clone()).is_err())
+}
+
+/// Extracts the images of the sources that can't be copied because their export node is
+/// missing from the render graph.
+///
+/// Render targets are skipped, as their CPU-side data is never updated with what's rendered
+/// into them, as are images without data or only zeroed data. Images are only extracted again
+/// once they changed.
+fn extract_cpu_fallback_images(
+    sources: Extract<Res<Assets<ImageExportSource>>>,
+    images: Extract<Res<Assets<Image>>>,
+    mut image_events: Extract<EventReader<AssetEvent<Image>>>,
+    gpu_sources: Res<RenderAssets<ImageExportSource>>,
+    graph: Res<RenderGraph>,
+    mut fallback: ResMut<CpuFallbackImages>,
+    mut warned: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    let changed: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } | AssetEvent::Removed { id } => {
+                Some(*id)
+            }
+            _ => None,
+        })
+        .collect();
+    let mut previous = std::mem::take(&mut fallback.0);
+    for (id, gpu_source) in gpu_sources.iter() {
+        if gpu_source.copied_frame.load(Ordering::Acquire) != u32::MAX
+            || !export_node_missing(&graph, gpu_source)
+        {
+            continue;
+        }
+        let Some(source) = sources.get(id) else {
+            continue;
         };
-        return Some(img);
+        let image_id = source.image_handle().id();
+        let image = match previous.remove(&id) {
+            Some((previous_id, image))
+                if previous_id == image_id && !changed.contains(&image_id) =>
+            {
+                image
+            }
+            _ => images
+                .get(image_id)
+                .filter(|image| {
+                    !image
+                        .texture_descriptor
+                        .usage
+                        .contains(TextureUsages::RENDER_ATTACHMENT)
+                        && image.data.iter().any(|&byte| byte != 0)
+                })
+                .cloned(),
+        };
+        if image.is_some() && warned.insert(id) {
+            warn!(
+                "the export node of image source {id:?} is missing from the render graph, exporting the CPU-side data of its image instead"
+            );
+        }
+        fallback.0.insert(id, (image_id, image));
+    }
+}
+
+/// How long reading a frame back may take before it's dropped, e.g. because the device hung
+/// or was lost.
+#[derive(Resource, Clone, Copy, Debug)]
+pub(crate) struct ReadbackTimeout(pub Duration);
+
+impl Default for ReadbackTimeout {
+    fn default() -> Self {
+        Self(Duration::from_secs(5))
+    }
+}
+
+/// Setup shared by [`ImageExportPlugin`] and [`NDIExportPlugin`](crate::NDIExportPlugin): the
+/// source asset, its extraction and readback, and the export nodes. Add it with
+/// [`add_core_plugin`] so that it's only added once when both plugins are used.
+struct ImageExportCorePlugin;
+
+impl Plugin for ImageExportCorePlugin {
+    fn build(&self, app: &mut App) {
+        use ImageExportRenderSystems::*;
+
+        app.register_type::<ImageExportSource>()
+            .init_asset::<ImageExportSource>()
+            .register_asset_reflect::<ImageExportSource>()
+            .add_systems(
+                PostUpdate,
+                (
+                    (apply_export_clear_colors, check_transparent_exports).chain(),
+                    apply_export_render_layers,
+                    switch_export_sources,
+                )
+                    .before(CameraUpdateSystem),
+            )
+            .add_plugins((
+                RenderAssetPlugin::<ImageExportSource, Image>::default(),
+                ExtractComponentPlugin::<AlphaMatte>::default(),
+                ExtractComponentPlugin::<StereoExport>::default(),
+            ));
+        add_export_control(app);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<RejectedExportSources>()
+            .init_resource::<ExportedFrames>()
+            .init_resource::<ReadbackTimeout>()
+            .init_resource::<SyncCaptures>()
+            .init_resource::<CpuFallbackImages>()
+            .add_systems(ExtractSchedule, extract_cpu_fallback_images)
+            .configure_sets(
+                Render,
+                (Readback, PostReadback, Export)
+                    .chain()
+                    .after(RenderSet::Render)
+                    .before(RenderSet::Cleanup),
+            )
+            .add_systems(
+                Render,
+                (read_back_frames, compose_stereo_frames, apply_alpha_mattes)
+                    .chain()
+                    .in_set(Readback),
+            );
+
+        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+        add_image_export_node(&mut graph, &[CAMERA_DRIVER], &[]);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // The render device and adapter are only available once the renderer is initialized.
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app.init_resource::<AdapterDiagnostics>();
+        // Added once all plugins are built, so that the nodes of e.g. `bevy_ui` exist whether
+        // its plugin is added before or after this one.
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        add_camera_export_nodes(&mut graph);
+    }
+}
+
+/// Adds [`ImageExportCorePlugin`], unless another export plugin did already.
+pub(crate) fn add_core_plugin(app: &mut App) {
+    if !app.is_plugin_added::<ImageExportCorePlugin>() {
+        app.add_plugins(ImageExportCorePlugin);
     }
-    None
 }
 
 /// Plugin enabling the generation of image sequences.
+///
+/// The default plugin only reads frames back for the streaming exports, such as
+/// [`ImageExportDestination`] or [`FifoExport`]. Use [`ImageExportPlugin::disk`] to also write
+/// the frames of exports with [`ImageExportSettings`] to disk.
 #[derive(Default)]
-pub struct ImageExportPlugin;
+pub struct ImageExportPlugin {
+    disk_root: Option<PathBuf>,
+    timing_log: Option<PathBuf>,
+    readback_timeout: Option<Duration>,
+}
+
+impl ImageExportPlugin {
+    /// Writes frames to disk, into the `output_dir` of each export relative to `root`.
+    pub fn disk(root: impl Into<PathBuf>) -> Self {
+        Self {
+            disk_root: Some(root.into()),
+            ..default()
+        }
+    }
+
+    /// Writes a CSV file with a row per frame captured or dropped by a disk export, holding
+    /// its timestamp, readback duration and encode duration. The file is replaced on startup.
+    pub fn with_timing_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.timing_log = Some(path.into());
+        self
+    }
+
+    /// Drops a frame if reading it back from the GPU takes longer than `timeout`, logging an
+    /// error instead of freezing the app when the device hangs. Defaults to 5 seconds.
+    pub fn with_readback_timeout(mut self, timeout: Duration) -> Self {
+        self.readback_timeout = Some(timeout);
+        self
+    }
+}
 
+/// Main world system sets of [`ImageExportPlugin`], running in `PostUpdate` before the cameras
+/// are updated.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum ImageExportSystems {
+    /// Sets up exports, e.g. adds the bundles of cameras marked with
+    /// [`ExportThisCamera`](crate::ExportThisCamera). Systems spawning exports can run here.
     SetupImageExport,
+    /// Applies the commands of `SetupImageExport`, so that exports spawned there are visible to
+    /// the camera update of the same frame.
     SetupImageExportFlush,
 }
 
@@ -204,26 +1390,258 @@ impl Plugin for ImageExportPlugin {
                 .chain()
                 .before(CameraUpdateSystem),
         )
-        .register_type::<ImageExportSource>()
-        .init_asset::<ImageExportSource>()
-        .register_asset_reflect::<ImageExportSource>()
+        .add_systems(PostUpdate, apply_deferred.in_set(SetupImageExportFlush))
+        .add_event::<CaptureRequest>()
+        .add_event::<ResetCapture>()
+        .add_event::<FrameExported>()
+        .add_event::<ExportFinished>()
+        .add_event::<FrameVerificationFailed>()
+        .add_systems(
+            PreUpdate,
+            (receive_export_messages, update_achieved_fps).chain(),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                handle_capture_requests,
+                handle_capture_resets,
+                update_export_viewports,
+                export_marked_cameras,
+                warn_shared_targets,
+                apply_supersampling,
+                adapt_export_quality,
+                update_recording_sessions,
+                resize_gpu_destinations,
+                resolve_export_extensions,
+            )
+                .in_set(SetupImageExport),
+        )
+        .add_systems(PostUpdate, apply_source_retention.in_set(SetupImageExport))
+        .add_systems(
+            PostUpdate,
+            (
+                update_frame_sidecars.after(TransformSystem::TransformPropagate),
+                update_export_projections,
+            )
+                .after(CameraUpdateSystem),
+        )
         .add_plugins((
-            RenderAssetPlugin::<ImageExportSource>::default(),
             ExtractComponentPlugin::<ImageExport>::default(),
+            ExtractComponentPlugin::<ImageExportDestination>::default(),
+            ExtractComponentPlugin::<FrameSidecar>::default(),
+            ExtractComponentPlugin::<ExportViewports>::default(),
+            ExtractComponentPlugin::<FifoExport>::default(),
+            ExtractComponentPlugin::<MipChainExport>::default(),
+            ExtractComponentPlugin::<FrameCacheExport>::default(),
+            ExtractComponentPlugin::<ChannelExport>::default(),
+            ExtractComponentPlugin::<ExportProjection>::default(),
         ));
+        #[cfg(feature = "upload")]
+        app.add_plugins(ExtractComponentPlugin::<UploadExport>::default());
+        #[cfg(feature = "websocket")]
+        app.add_plugins(ExtractComponentPlugin::<WebSocketExport>::default());
+        #[cfg(feature = "mmap")]
+        app.add_plugins(ExtractComponentPlugin::<MmapExport>::default());
 
-        let render_app = app.sub_app_mut(RenderApp);
+        add_core_plugin(app);
+
+        let (sender, receiver) = export_message_channel();
+        app.insert_resource(receiver);
 
+        let render_app = app.sub_app_mut(RenderApp);
+        if let Some(timeout) = self.readback_timeout {
+            render_app.insert_resource(ReadbackTimeout(timeout));
+        }
+        render_app
+            .insert_resource(sender)
+            .init_resource::<GpuDestinations>()
+            .init_resource::<ExtractedFrameCache>()
+            .add_systems(
+                ExtractSchedule,
+                (extract_gpu_destinations, extract_frame_cache),
+            )
+            .add_systems(
+                Render,
+                (
+                    copy_to_destinations,
+                    write_to_fifos,
+                    write_mip_chains,
+                    send_cached_frames,
+                    send_to_channels,
+                )
+                    .in_set(ImageExportRenderSystems::Export),
+            );
+        if let Some(path) = self
+            .timing_log
+            .as_ref()
+            .filter(|_| self.disk_root.is_some())
+        {
+            match TimingLog::create(path) {
+                Ok(log) => {
+                    render_app.insert_resource(log);
+                }
+                Err(e) => error!("failed to create timing log {}: {e}", path.display()),
+            }
+        }
+        if let Some(root) = &self.disk_root {
+            render_app
+                .insert_resource(DiskExportRoot(root.clone()))
+                .init_resource::<DiskExportStates>()
+                .add_systems(
+                    Render,
+                    (
+                        pace_disk_exports
+                            .in_set(ImageExportRenderSystems::Readback)
+                            .before(read_back_frames),
+                        save_buffer_to_disk.in_set(ImageExportRenderSystems::Export),
+                    ),
+                );
+        }
+        #[cfg(feature = "upload")]
+        render_app.add_systems(
+            Render,
+            upload_frames.in_set(ImageExportRenderSystems::Export),
+        );
+        #[cfg(feature = "websocket")]
         render_app.add_systems(
             Render,
-            save_buffer_to_disk
-                .after(RenderSet::Render)
-                .before(RenderSet::Cleanup),
+            send_to_websockets.in_set(ImageExportRenderSystems::Export),
         );
+        #[cfg(feature = "mmap")]
+        render_app.add_systems(
+            Render,
+            write_to_mmap_files.in_set(ImageExportRenderSystems::Export),
+        );
+    }
+}
 
-        let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// Counts the allocations and allocated bytes of every thread, so tests running in parallel
+    /// don't disturb each other.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|allocated| {
+                let (count, bytes) = allocated.get();
+                allocated.set((count + 1, bytes + layout.size()));
+            });
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
 
-        graph.add_node(NODE_NAME, ImageExportNode);
-        graph.add_node_edge(CAMERA_DRIVER, NODE_NAME);
+    fn allocated() -> (usize, usize) {
+        ALLOCATED.with(Cell::get)
+    }
+
+    /// Reads a frame back like `read_back_frames` and shares it with a sink.
+    fn read_back(frames: &mut ExportedFrames, mapped: &[u8], padded_bytes_per_row: u32) {
+        let id = AssetId::<ImageExportSource>::default();
+        let size = Extent3d {
+            width: 30,
+            height: 20,
+            depth_or_array_layers: 1,
+        };
+        frames.recycle();
+        let buffer = frames.buffer(id);
+        copy_readback(mapped, 30 * 4, padded_bytes_per_row, false, buffer);
+        let image = readback_image(std::mem::take(buffer), size, TextureFormat::Rgba8UnormSrgb);
+        frames.insert(id, image, None, Duration::ZERO);
+        let shared = frames.get_export_shared(Entity::from_raw(0), id).unwrap();
+        assert_eq!(shared.data.len(), 30 * 4 * 20);
+    }
+
+    #[test]
+    fn readback_reuses_frame_data() {
+        const FRAMES: usize = 10_000;
+        for padded_bytes_per_row in [30 * 4, 256] {
+            let mapped = vec![7; padded_bytes_per_row as usize * 20];
+            let mut frames = ExportedFrames::default();
+            for _ in 0..10 {
+                read_back(&mut frames, &mapped, padded_bytes_per_row);
+            }
+
+            let (count, bytes) = allocated();
+            for _ in 0..FRAMES {
+                read_back(&mut frames, &mapped, padded_bytes_per_row);
+            }
+            let (count, bytes) = (allocated().0 - count, allocated().1 - bytes);
+            // Only the `Arc` of every frame is allocated, its data is reused.
+            assert!(count <= FRAMES, "{count} allocations for {FRAMES} frames");
+            assert!(
+                bytes <= FRAMES * 512,
+                "{bytes} bytes allocated for {FRAMES} frames"
+            );
+        }
+    }
+
+    #[test]
+    fn copy_readback_removes_padding() {
+        let mapped = [1, 2, 3, 0, 4, 5, 6, 0];
+        let mut buffer = Vec::new();
+        copy_readback(&mapped, 3, 4, false, &mut buffer);
+        assert_eq!(buffer, [1, 2, 3, 4, 5, 6]);
+        copy_readback(&mapped, 3, 4, true, &mut buffer);
+        assert_eq!(buffer, mapped);
+        copy_readback(&mapped, 4, 4, false, &mut buffer);
+        assert_eq!(buffer, mapped);
+    }
+
+    #[test]
+    fn readback_layout_reports_rows_too_large_to_copy() {
+        let size = |width, height| Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let error =
+            readback_layout(size(u32::MAX, 1), TextureFormat::Rgba32Float, u64::MAX).unwrap_err();
+        assert!(error.contains("a row of"), "{error}");
+        let error = readback_layout(size(256, 256), TextureFormat::Rgba8Unorm, 1024).unwrap_err();
+        assert!(error.contains("device limit of 1024 bytes"), "{error}");
+        assert_eq!(
+            readback_layout(size(30, 20), TextureFormat::Rgba8Unorm, u64::MAX),
+            Ok((120, 256, 256 * 20))
+        );
+    }
+
+    #[test]
+    fn exports_keep_their_source_alive_only_if_retained() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<ImageExportSource>()
+            .add_systems(Update, apply_source_retention);
+        let mut sources = app.world.resource_mut::<Assets<ImageExportSource>>();
+        let (retained, weak) = (sources.add(default()), sources.add(default()));
+        let (retained_id, weak_id) = (retained.id(), weak.id());
+        app.world
+            .spawn((retained.clone_weak(), ImageExport::default()));
+        app.world
+            .spawn((weak.clone(), ImageExport::with_weak_source()));
+        app.update();
+
+        drop((retained, weak));
+        for _ in 0..3 {
+            app.update();
+        }
+        let sources = app.world.resource::<Assets<ImageExportSource>>();
+        assert!(sources.contains(retained_id));
+        assert!(!sources.contains(weak_id));
     }
 }
@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+/// Paces the frames of an export to a wall-clock rate, independently of the render rate.
+///
+/// Frames are scheduled in exact steps of `interval`, so fractional rates like 29.97fps don't
+/// drift. The schedule restarts when the app falls behind by more than a frame.
+#[derive(Clone, Debug)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    next_frame: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_frame: None,
+        }
+    }
+
+    /// The time between frames.
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Limits frames to `numerator / denominator` per second. Panics if `numerator` is 0.
+    pub(crate) fn from_frame_rate((numerator, denominator): (u32, u32)) -> Self {
+        Self::new(Duration::from_nanos(
+            1_000_000_000 * denominator as u64 / numerator as u64,
+        ))
+    }
+
+    /// Whether a frame is due at `now`, scheduling the next one if so.
+    pub(crate) fn ready(&mut self, now: Instant) -> bool {
+        match self.next_frame {
+            Some(next_frame) if now < next_frame => false,
+            Some(next_frame) if now < next_frame + self.interval => {
+                self.next_frame = Some(next_frame + self.interval);
+                true
+            }
+            _ => {
+                self.next_frame = Some(now + self.interval);
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use image::{imageops, imageops::FilterType, DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::convert::{linear_to_srgb, srgb_to_linear};
+
+/// Filter used to resample frames when resizing them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Fastest, keeps hard pixel edges, e.g. for pixel art.
+    Nearest,
+    #[default]
+    Triangle,
+    CatmullRom,
+    /// Slowest, sharpest results for photographic content.
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn filter_type(self) -> FilterType {
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Resizes frames to a fixed size before they are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResizeSettings {
+    pub width: u32,
+    pub height: u32,
+    pub filter: ResizeFilter,
+}
+
+impl ResizeSettings {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            filter: ResizeFilter::default(),
+        }
+    }
+
+    /// Settings scaling an image of the given size by `scale`, rounded to even dimensions as
+    /// most video encoders require.
+    pub(crate) fn scaled(width: u32, height: u32, scale: f32) -> Self {
+        let even = |size: u32| (((size as f32 * scale / 2.0).round() as u32) * 2).max(2);
+        Self::new(even(width), even(height))
+    }
+
+    /// Resizes `image`, returning `None` if it already has the target size.
+    ///
+    /// The colors of `srgb` images are resampled in linear space, as averaging sRGB encoded
+    /// values darkens the result.
+    pub(crate) fn apply(&self, image: &DynamicImage, srgb: bool) -> Option<DynamicImage> {
+        if (image.width(), image.height()) == (self.width, self.height) {
+            return None;
+        }
+        if !srgb || self.filter == ResizeFilter::Nearest {
+            return Some(image.resize_exact(self.width, self.height, self.filter.filter_type()));
+        }
+
+        let decode: Vec<f32> = (0..=255)
+            .map(|value| srgb_to_linear(value as f32 / 255.0))
+            .collect();
+        let rgba = image.to_rgba8();
+        let linear = Rgba32FImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            Rgba([
+                decode[r as usize],
+                decode[g as usize],
+                decode[b as usize],
+                a as f32 / 255.0,
+            ])
+        });
+        let resized = imageops::resize(&linear, self.width, self.height, self.filter.filter_type());
+        let encode = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Some(DynamicImage::ImageRgba8(RgbaImage::from_fn(
+            self.width,
+            self.height,
+            |x, y| {
+                let [r, g, b, a] = resized.get_pixel(x, y).0;
+                Rgba([
+                    encode(linear_to_srgb(r)),
+                    encode(linear_to_srgb(g)),
+                    encode(linear_to_srgb(b)),
+                    encode(a),
+                ])
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_rounds_to_even_sizes() {
+        assert_eq!(
+            ResizeSettings::scaled(1920, 1080, 0.5),
+            ResizeSettings::new(960, 540)
+        );
+        assert_eq!(
+            ResizeSettings::scaled(101, 51, 0.5),
+            ResizeSettings::new(50, 26)
+        );
+        assert_eq!(ResizeSettings::scaled(1, 1, 0.1), ResizeSettings::new(2, 2));
+        assert_eq!(
+            ResizeSettings::scaled(640, 480, 2.0),
+            ResizeSettings::new(1280, 960)
+        );
+    }
+
+    #[test]
+    fn resamples_srgb_in_linear_space() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| {
+            Rgba([if x == 0 { 0 } else { 255 }, 0, 0, 255])
+        }));
+        let resize = ResizeSettings::new(1, 1);
+        assert!(ResizeSettings::new(2, 1).apply(&image, true).is_none());
+
+        // Half of the linear intensity is 188 in sRGB, not 128.
+        let srgb = resize.apply(&image, true).unwrap().to_rgba8();
+        assert_eq!(srgb.get_pixel(0, 0).0, [188, 0, 0, 255]);
+        let linear = resize.apply(&image, false).unwrap().to_rgba8();
+        assert_eq!(linear.get_pixel(0, 0).0, [128, 0, 0, 255]);
+    }
+}
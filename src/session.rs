@@ -0,0 +1,109 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use bevy::prelude::*;
+
+use crate::events::{ExportFinished, ExportFinishedReason, ExportStats};
+use crate::plugin::{ImageExport, ImageExportBundle};
+
+/// Handle to a disk export started with [`RecordingSession::start`], controlling the recording
+/// from anywhere, e.g. a tool's UI or another thread. Clones control the same recording.
+///
+/// Requests are applied at the start of the next frame's `PostUpdate`.
+#[derive(Clone, Debug)]
+pub struct RecordingSession {
+    entity: Entity,
+    state: Arc<SessionState>,
+}
+
+#[derive(Debug, Default)]
+struct SessionState {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    frames_written: AtomicU64,
+}
+
+impl RecordingSession {
+    /// Spawns the export `bundle` and returns the session recording it.
+    pub fn start(commands: &mut Commands, bundle: ImageExportBundle) -> Self {
+        let state = Arc::<SessionState>::default();
+        let entity = commands
+            .spawn((bundle, RecordingSessionLink(state.clone())))
+            .id();
+        Self { entity, state }
+    }
+
+    /// The exporting entity.
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Stops the recording for good: the [`ImageExport`] component is removed and
+    /// [`ExportFinished`] is sent. Frames captured before are still written.
+    pub fn stop(&self) {
+        self.state.stopped.store(true, Ordering::Release);
+    }
+
+    /// Skips frames until [`resume`](Self::resume) is called, without advancing the frame
+    /// numbering.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.state.paused.store(false, Ordering::Release);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Acquire)
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.stopped.load(Ordering::Acquire)
+    }
+
+    /// Number of frames written to disk so far, see [`ExportStats`]. Keeps counting the frames
+    /// still in flight after [`stop`](Self::stop).
+    pub fn frames_written(&self) -> u64 {
+        self.state.frames_written.load(Ordering::Acquire)
+    }
+}
+
+/// Links an export to the state shared with its [`RecordingSession`]s.
+#[derive(Component)]
+pub(crate) struct RecordingSessionLink(Arc<SessionState>);
+
+pub(crate) fn update_recording_sessions(
+    mut commands: Commands,
+    mut sessions: Query<(
+        Entity,
+        &RecordingSessionLink,
+        Option<&mut ImageExport>,
+        &ExportStats,
+    )>,
+    mut finished: EventWriter<ExportFinished>,
+) {
+    for (entity, link, export, stats) in &mut sessions {
+        let state = &link.0;
+        state
+            .frames_written
+            .store(stats.frames_written, Ordering::Release);
+        let Some(mut export) = export else {
+            continue;
+        };
+        if state.stopped.load(Ordering::Acquire) {
+            commands.entity(entity).remove::<ImageExport>();
+            finished.send(ExportFinished {
+                entity,
+                reason: ExportFinishedReason::Stopped,
+            });
+            continue;
+        }
+        let paused = state.paused.load(Ordering::Acquire);
+        if export.paused != paused {
+            export.paused = paused;
+        }
+    }
+}
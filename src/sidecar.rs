@@ -0,0 +1,286 @@
+use std::{fmt::Write, path::Path};
+
+use bevy::{
+    core::FrameCount,
+    core_pipeline::tonemapping::Tonemapping,
+    ecs::{query::QueryItem, system::SystemChangeTick},
+    prelude::*,
+    render::{camera::RenderTarget, extract_component::ExtractComponent, view::ColorGrading},
+};
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::ImageExportSource;
+
+/// Writes a JSON file next to every frame saved to disk, recording the exposure and tonemapping
+/// of the camera rendering into the export's source.
+///
+/// The sidecar also holds the render frame, as counted by [`FrameCount`], and the ECS change
+/// tick the camera parameters were captured at, to correlate frames with the simulation state.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SidecarSettings {
+    /// Also records the camera's global transform as a column-major 4x4 matrix.
+    pub include_transform: bool,
+    /// Also records the camera's projection parameters and its column-major projection
+    /// matrix, e.g. to reconstruct camera poses and intrinsics for NeRF or photogrammetry
+    /// datasets. Perspective cameras also get the horizontal and vertical focal length in
+    /// pixels of the written frame, after supersampling, rotation and resizing, along with its
+    /// size as `image_size`. Renditions and split viewports are scaled or cropped from a frame
+    /// of that size.
+    pub include_projection: bool,
+}
+
+/// The written frame a sidecar describes.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SidecarImage {
+    pub size: UVec2,
+    /// Whether the frame was rotated by a quarter turn, so its width shows the camera's height.
+    pub swapped_axes: bool,
+}
+
+impl SidecarImage {
+    /// Focal length in pixels along the axes of the frame, for a camera with a vertical field
+    /// of view of `fov_y`.
+    fn focal_length(self, fov_y: f32, aspect_ratio: f32) -> Vec2 {
+        let (width, height) = if self.swapped_axes {
+            (self.size.y, self.size.x)
+        } else {
+            (self.size.x, self.size.y)
+        };
+        let tan = (0.5 * fov_y).tan();
+        let focal_length = Vec2::new(
+            0.5 * width as f32 / (aspect_ratio * tan),
+            0.5 * height as f32 / tan,
+        );
+        if self.swapped_axes {
+            Vec2::new(focal_length.y, focal_length.x)
+        } else {
+            focal_length
+        }
+    }
+}
+
+/// Projection parameters of the camera, as recorded in the sidecar.
+#[derive(Clone, Copy, Debug)]
+enum SidecarProjection {
+    Perspective {
+        fov_y: f32,
+        aspect_ratio: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        near: f32,
+        far: f32,
+        scale: f32,
+        area: Rect,
+    },
+}
+
+/// Camera parameters of the current frame, captured in the main world for the sidecar.
+#[derive(Component, Clone, Debug)]
+pub(crate) struct FrameSidecar {
+    exposure: f32,
+    gamma: f32,
+    tonemapping: Option<Tonemapping>,
+    transform: Option<Mat4>,
+    projection: Option<(Option<SidecarProjection>, Mat4)>,
+    timestamp: f64,
+    render_frame: u32,
+    tick: u32,
+}
+
+impl ExtractComponent for FrameSidecar {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(item.clone())
+    }
+}
+
+impl FrameSidecar {
+    fn to_json(&self, frame: u32, exposure_scale: Option<[f32; 3]>, image: SidecarImage) -> String {
+        let mut json = format!(
+            "{{\n  \"frame\": {frame},\n  \"render_frame\": {},\n  \"tick\": {},\n  \"timestamp\": {},\n  \"exposure\": {},\n  \"gamma\": {}",
+            self.render_frame, self.tick, self.timestamp, self.exposure, self.gamma
+        );
+        match self.tonemapping {
+            Some(tonemapping) => write!(json, ",\n  \"tonemapping\": \"{tonemapping:?}\""),
+            None => write!(json, ",\n  \"tonemapping\": null"),
+        }
+        .unwrap();
+        if let Some([r, g, b]) = exposure_scale {
+            write!(json, ",\n  \"exposure_scale\": [{r}, {g}, {b}]").unwrap();
+        }
+        if let Some(transform) = self.transform {
+            write!(json, ",\n  \"transform\": {}", matrix_json(transform)).unwrap();
+        }
+        if let Some((projection, matrix)) = self.projection {
+            match projection {
+                Some(SidecarProjection::Perspective {
+                    fov_y,
+                    aspect_ratio,
+                    near,
+                    far,
+                }) => {
+                    let focal_length = image.focal_length(fov_y, aspect_ratio);
+                    write!(
+                        json,
+                        ",\n  \"projection\": {{ \"type\": \"perspective\", \"fov_y\": {fov_y}, \"aspect_ratio\": {aspect_ratio}, \"near\": {near}, \"far\": {far}, \"focal_length\": [{}, {}], \"image_size\": [{}, {}] }}",
+                        focal_length.x, focal_length.y, image.size.x, image.size.y
+                    )
+                }
+                Some(SidecarProjection::Orthographic {
+                    near,
+                    far,
+                    scale,
+                    area,
+                }) => write!(
+                    json,
+                    ",\n  \"projection\": {{ \"type\": \"orthographic\", \"near\": {near}, \"far\": {far}, \"scale\": {scale}, \"area\": [{}, {}, {}, {}] }}",
+                    area.min.x, area.min.y, area.max.x, area.max.y
+                ),
+                None => write!(json, ",\n  \"projection\": null"),
+            }
+            .unwrap();
+            write!(json, ",\n  \"projection_matrix\": {}", matrix_json(matrix)).unwrap();
+        }
+        json.push_str("\n}\n");
+        json
+    }
+
+    /// Writes the sidecar of `frame` next to the `image` at `image_path`, along with the
+    /// per-channel scale applied by [`AutoExposure`](crate::AutoExposure), if any.
+    pub(crate) fn write(
+        &self,
+        image_path: &Path,
+        frame: u32,
+        exposure_scale: Option<[f32; 3]>,
+        image: SidecarImage,
+    ) -> std::io::Result<()> {
+        std::fs::write(
+            image_path.with_extension("json"),
+            self.to_json(frame, exposure_scale, image),
+        )
+    }
+}
+
+/// Formats a matrix as a JSON array of its columns' values.
+fn matrix_json(matrix: Mat4) -> String {
+    let values: Vec<_> = matrix.to_cols_array().iter().map(f32::to_string).collect();
+    format!("[{}]", values.join(", "))
+}
+
+/// The projection parameters of a camera, or `None` for custom projections.
+fn sidecar_projection(
+    projection: Option<&Projection>,
+    perspective: Option<&PerspectiveProjection>,
+    orthographic: Option<&OrthographicProjection>,
+) -> Option<SidecarProjection> {
+    let perspective = match projection {
+        Some(Projection::Perspective(perspective)) => Some(perspective),
+        Some(Projection::Orthographic(_)) => None,
+        None => perspective,
+    };
+    let orthographic = match projection {
+        Some(Projection::Orthographic(orthographic)) => Some(orthographic),
+        Some(Projection::Perspective(_)) => None,
+        None => orthographic,
+    };
+    if let Some(perspective) = perspective {
+        return Some(SidecarProjection::Perspective {
+            fov_y: perspective.fov,
+            aspect_ratio: perspective.aspect_ratio,
+            near: perspective.near,
+            far: perspective.far,
+        });
+    }
+    orthographic.map(|orthographic| SidecarProjection::Orthographic {
+        near: orthographic.near,
+        far: orthographic.far,
+        scale: orthographic.scale,
+        area: orthographic.area,
+    })
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_frame_sidecars(
+    mut commands: Commands,
+    exports: Query<(Entity, &Handle<ImageExportSource>, &ImageExportSettings)>,
+    sources: Res<Assets<ImageExportSource>>,
+    cameras: Query<(
+        &Camera,
+        &GlobalTransform,
+        Option<&ColorGrading>,
+        Option<&Tonemapping>,
+        (
+            Option<&Projection>,
+            Option<&PerspectiveProjection>,
+            Option<&OrthographicProjection>,
+        ),
+    )>,
+    time: Res<Time>,
+    frame_count: Res<FrameCount>,
+    ticks: SystemChangeTick,
+) {
+    for (entity, source_handle, settings) in &exports {
+        let Some(sidecar) = settings.sidecar else {
+            continue;
+        };
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        let Some((camera, transform, color_grading, tonemapping, (projection, perspective, orthographic))) = cameras.iter().find(|(camera, ..)| {
+            matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+        }) else {
+            continue;
+        };
+        let color_grading = color_grading.copied().unwrap_or_default();
+        commands.entity(entity).insert(FrameSidecar {
+            exposure: color_grading.exposure,
+            gamma: color_grading.gamma,
+            tonemapping: tonemapping.copied(),
+            transform: sidecar
+                .include_transform
+                .then(|| transform.compute_matrix()),
+            projection: sidecar.include_projection.then(|| {
+                (
+                    sidecar_projection(projection, perspective, orthographic),
+                    camera.projection_matrix(),
+                )
+            }),
+            timestamp: time.elapsed_seconds_f64(),
+            render_frame: frame_count.0,
+            tick: ticks.this_run().get(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn focal_length_matches_the_written_frame() {
+        // A 90° view is half as wide as the focal length.
+        let image = SidecarImage {
+            size: UVec2::new(200, 100),
+            swapped_axes: false,
+        };
+        assert!(image
+            .focal_length(FRAC_PI_2, 2.0)
+            .abs_diff_eq(Vec2::splat(50.0), 1e-4));
+
+        // Stretched horizontally, then turned on its side.
+        let image = SidecarImage {
+            size: UVec2::new(100, 400),
+            swapped_axes: true,
+        };
+        assert!(image
+            .focal_length(FRAC_PI_2, 2.0)
+            .abs_diff_eq(Vec2::new(50.0, 100.0), 1e-4));
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(feature = "burn_in")]
+use bevy::{core::FrameCount, render::render_resource::TextureFormat};
+use bevy::{ecs::query::QueryItem, prelude::*, render::extract_component::ExtractComponent};
+#[cfg(feature = "burn_in")]
+use image::{imageops, DynamicImage, RgbaImage};
+
+#[cfg(feature = "burn_in")]
+use crate::burn_in::BurnInSettings;
+use crate::convert::with_data;
+use crate::plugin::{ExportedFrames, ImageExportSource};
+
+/// How the eyes of a [`StereoExport`] are arranged in the exported frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// The left eye on the left, the right eye on the right, doubling the width.
+    #[default]
+    SideBySide,
+    /// The left eye on top, the right eye at the bottom, doubling the height.
+    TopBottom,
+}
+
+/// Exports the frames of the entity's source, the left eye, and of `right_eye` as a single
+/// frame, e.g. for VR capture.
+///
+/// Both eyes are read back together and frames are only exported once both are available.
+/// The eyes must have the same size and format. Only the sinks on the entity that read frames
+/// back export the composed frame, other exports of the eyes' sources are unaffected.
+#[derive(Component, Clone, Debug)]
+pub struct StereoExport {
+    pub right_eye: Handle<ImageExportSource>,
+    pub layout: StereoLayout,
+    /// Text drawn into each eye, with `{eye}` in the template replaced by `L` or `R` and
+    /// `{frame}` by the render frame. Only 8-bit RGBA frames are labeled.
+    #[cfg(feature = "burn_in")]
+    pub labels: Option<BurnInSettings>,
+}
+
+impl StereoExport {
+    pub fn new(right_eye: Handle<ImageExportSource>, layout: StereoLayout) -> Self {
+        Self {
+            right_eye,
+            layout,
+            #[cfg(feature = "burn_in")]
+            labels: None,
+        }
+    }
+}
+
+impl ExtractComponent for StereoExport {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(item.clone())
+    }
+}
+
+pub(crate) fn compose_stereo_frames(
+    exports: Query<(Entity, &Handle<ImageExportSource>, &StereoExport)>,
+    #[cfg(feature = "burn_in")] frame_count: Res<FrameCount>,
+    mut frames: ResMut<ExportedFrames>,
+) {
+    for (entity, source_handle, stereo) in &exports {
+        let id = source_handle.id();
+        let Some(left) = frames.get_unpadded(id) else {
+            continue;
+        };
+        let Some(right) = frames.get_unpadded(stereo.right_eye.id()) else {
+            debug!("dropping stereo frame of export {entity:?}, its right eye isn't ready");
+            frames.compose(entity, None);
+            continue;
+        };
+        let Some(image) = compose_eyes(&left, &right, stereo.layout) else {
+            warn!(
+                "dropping stereo frame of export {entity:?}, its right eye has a different size or format"
+            );
+            frames.compose(entity, None);
+            continue;
+        };
+        #[cfg(feature = "burn_in")]
+        let mut image = image;
+        #[cfg(feature = "burn_in")]
+        if let Some(labels) = &stereo.labels {
+            label_eyes(&mut image, labels, stereo.layout, frame_count.0);
+        }
+        frames.compose(entity, Some(image));
+    }
+}
+
+/// Arranges both eyes in a single frame, or returns `None` if they differ in size or format.
+fn compose_eyes(left: &Image, right: &Image, layout: StereoLayout) -> Option<Image> {
+    if left.texture_descriptor.size != right.texture_descriptor.size
+        || left.texture_descriptor.format != right.texture_descriptor.format
+    {
+        return None;
+    }
+
+    let mut size = left.texture_descriptor.size;
+    let data = match layout {
+        StereoLayout::SideBySide => {
+            size.width *= 2;
+            let row = left.data.len() / left.height().max(1) as usize;
+            left.data
+                .chunks(row)
+                .zip(right.data.chunks(row))
+                .flat_map(|(left, right)| [left, right])
+                .flatten()
+                .copied()
+                .collect()
+        }
+        StereoLayout::TopBottom => {
+            size.height *= 2;
+            [left.data.as_slice(), &right.data].concat()
+        }
+    };
+    let mut image = with_data(left, left.texture_descriptor.format, data);
+    image.texture_descriptor.size = size;
+    Some(image)
+}
+
+#[cfg(feature = "burn_in")]
+fn label_eyes(image: &mut Image, labels: &BurnInSettings, layout: StereoLayout, frame: u32) {
+    let format = image.texture_descriptor.format;
+    if !matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    ) {
+        warn!("cannot label the eyes of a stereo frame with format {format:?}");
+        return;
+    }
+    let (width, height) = (image.width(), image.height());
+    let Some(rgba) = RgbaImage::from_raw(width, height, std::mem::take(&mut image.data)) else {
+        return;
+    };
+    let mut dynamic = DynamicImage::ImageRgba8(rgba);
+    let (eye_width, eye_height, right) = match layout {
+        StereoLayout::SideBySide => (width / 2, height, UVec2::new(width / 2, 0)),
+        StereoLayout::TopBottom => (width, height / 2, UVec2::new(0, height / 2)),
+    };
+    for (eye, origin) in [("L", UVec2::ZERO), ("R", right)] {
+        let mut label = labels.clone();
+        label.template = label.template.replace("{eye}", eye);
+        let mut part = dynamic.crop_imm(origin.x, origin.y, eye_width, eye_height);
+        label.apply(&mut part, frame);
+        imageops::replace(&mut dynamic, &part, origin.x as i64, origin.y as i64);
+    }
+    image.data = dynamic.into_bytes();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    use super::*;
+
+    fn eye(pixels: [u8; 4], format: TextureFormat) -> Image {
+        let size = Extent3d {
+            width: 2,
+            height: 2,
+            depth_or_array_layers: 1,
+        };
+        Image::new(size, TextureDimension::D2, pixels.to_vec(), format)
+    }
+
+    #[test]
+    fn eyes_are_placed_by_layout() {
+        let left = eye([1, 2, 3, 4], TextureFormat::R8Unorm);
+        let right = eye([5, 6, 7, 8], TextureFormat::R8Unorm);
+
+        let side_by_side = compose_eyes(&left, &right, StereoLayout::SideBySide).unwrap();
+        assert_eq!((side_by_side.width(), side_by_side.height()), (4, 2));
+        assert_eq!(side_by_side.data, [1, 2, 5, 6, 3, 4, 7, 8]);
+
+        let top_bottom = compose_eyes(&left, &right, StereoLayout::TopBottom).unwrap();
+        assert_eq!((top_bottom.width(), top_bottom.height()), (2, 4));
+        assert_eq!(top_bottom.data, [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn eyes_of_different_size_or_format_are_not_composed() {
+        let left = eye([1, 2, 3, 4], TextureFormat::R8Unorm);
+        let mut smaller = eye([5, 6, 7, 8], TextureFormat::R8Unorm);
+        smaller.resize(Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        });
+        assert!(compose_eyes(&left, &smaller, StereoLayout::SideBySide).is_none());
+
+        let other_format = Image::new(
+            left.texture_descriptor.size,
+            TextureDimension::D2,
+            vec![0; 16],
+            TextureFormat::Rgba8Unorm,
+        );
+        assert!(compose_eyes(&left, &other_format, StereoLayout::TopBottom).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use bevy::{
+    prelude::*,
+    render::{camera::RenderTarget, render_resource::Extent3d, renderer::RenderDevice},
+    utils::{HashMap, HashSet},
+};
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::ImageExportSource;
+
+/// Private render target of a supersampled export.
+pub(crate) struct SupersampleTarget {
+    /// The export's own source, replaced by `source` while supersampling.
+    original: Handle<ImageExportSource>,
+    /// Image the cameras of the original source render into instead.
+    image: Handle<Image>,
+    source: Handle<ImageExportSource>,
+    size: Extent3d,
+}
+
+/// Renders the cameras of exports with [`ImageExportSettings::supersample`] into a private
+/// image enlarged by that factor, which the export reads back. The frames are scaled back down
+/// when they are written to disk; other sinks of the export, such as a
+/// [`FifoExport`](crate::FifoExport), an NDI sender or a
+/// [`ChannelExport`](crate::ChannelExport), receive the enlarged frames. The factor is lowered,
+/// with a warning, so the enlarged image fits in the device's maximum texture size.
+///
+/// The export's source handle is replaced by a private source of the enlarged image while
+/// supersampling, and restored along with the cameras' target once `supersample` is set back
+/// to 1. The original image keeps its size, but isn't rendered into meanwhile. If the app
+/// resizes the original image, the private one is enlarged again from its new size.
+pub(crate) fn apply_supersampling(
+    mut exports: Query<(Entity, &mut Handle<ImageExportSource>, &ImageExportSettings)>,
+    mut cameras: Query<&mut Camera>,
+    mut sources: ResMut<Assets<ImageExportSource>>,
+    mut images: ResMut<Assets<Image>>,
+    mut targets: Local<HashMap<Entity, SupersampleTarget>>,
+    render_device: Res<RenderDevice>,
+    mut warned: Local<HashSet<Entity>>,
+) {
+    // Hands the cameras of exports that stopped supersampling, were despawned, or were assigned
+    // another source back to the original image.
+    targets.retain(|&entity, target| {
+        let supersampled = exports.get(entity).is_ok_and(|(_, handle, settings)| {
+            settings.supersample > 1 && *handle == target.source
+        });
+        if !supersampled {
+            if let Ok((_, mut handle, _)) = exports.get_mut(entity) {
+                if *handle == target.source {
+                    *handle = target.original.clone();
+                }
+            }
+            if let Some(source) = sources.get(&target.original) {
+                retarget(&mut cameras, &target.image, source.image_handle());
+            }
+        }
+        supersampled
+    });
+
+    for (entity, mut handle, settings) in &mut exports {
+        if settings.supersample <= 1 {
+            continue;
+        }
+        let original = match targets.get(&entity) {
+            Some(target) => target.original.clone(),
+            None => handle.clone(),
+        };
+        let Some(source) = sources.get(&original).cloned() else {
+            continue;
+        };
+        let original_image = source.image_handle().clone();
+        let Some(image) = images.get(&original_image) else {
+            continue;
+        };
+        let size = image.texture_descriptor.size;
+        let max_dimension = render_device.limits().max_texture_dimension_2d;
+        let supersample = supported_supersample(settings.supersample, size, max_dimension);
+        if supersample == settings.supersample {
+            warned.remove(&entity);
+        } else if warned.insert(entity) {
+            warn!(
+                "supersampling {entity:?} {supersample} instead of {} times, its {}x{} image can't be enlarged beyond the device's maximum texture size of {max_dimension}",
+                settings.supersample, size.width, size.height
+            );
+        }
+        let enlarged = Extent3d {
+            width: size.width * supersample,
+            height: size.height * supersample,
+            ..size
+        };
+
+        match targets.get_mut(&entity) {
+            Some(target) if target.size == enlarged => {}
+            Some(target) => {
+                debug!(
+                    "resizing supersampled target of {entity:?} to {}x{}",
+                    enlarged.width, enlarged.height
+                );
+                if let Some(image) = images.get_mut(&target.image) {
+                    image.resize(enlarged);
+                }
+                target.size = enlarged;
+                // Replacing the source recreates its readback buffer for the new size.
+                if let Some(private_source) = sources.get(&target.source).cloned() {
+                    sources.insert(
+                        &target.source,
+                        private_source.supersampled(target.image.clone(), supersample),
+                    );
+                }
+            }
+            None => {
+                debug!(
+                    "supersampling {entity:?} at {}x{}",
+                    enlarged.width, enlarged.height
+                );
+                let mut private = Image {
+                    data: Vec::new(),
+                    texture_descriptor: image.texture_descriptor.clone(),
+                    sampler: image.sampler.clone(),
+                    texture_view_descriptor: image.texture_view_descriptor.clone(),
+                };
+                private.resize(enlarged);
+                let private = images.add(private);
+                let private_source = sources.add(source.supersampled(private.clone(), supersample));
+                *handle = private_source.clone();
+                targets.insert(
+                    entity,
+                    SupersampleTarget {
+                        original,
+                        image: private,
+                        source: private_source,
+                        size: enlarged,
+                    },
+                );
+            }
+        }
+        // Also picks up cameras spawned after the export started.
+        retarget(&mut cameras, &original_image, &targets[&entity].image);
+    }
+}
+
+/// The largest factor up to `supersample` that enlarges `size` within `max_dimension`, at
+/// least 1.
+fn supported_supersample(supersample: u32, size: Extent3d, max_dimension: u32) -> u32 {
+    let largest = size.width.max(size.height).max(1);
+    supersample.min(max_dimension / largest).max(1)
+}
+
+fn retarget(cameras: &mut Query<&mut Camera>, from: &Handle<Image>, to: &Handle<Image>) {
+    for mut camera in cameras {
+        if matches!(&camera.target, RenderTarget::Image(image) if image == from) {
+            camera.target = RenderTarget::Image(to.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supersampling_stays_within_the_texture_limit() {
+        let size = Extent3d {
+            width: 3000,
+            height: 1000,
+            depth_or_array_layers: 1,
+        };
+        assert_eq!(supported_supersample(2, size, 8192), 2);
+        assert_eq!(supported_supersample(4, size, 8192), 2);
+        assert_eq!(supported_supersample(4, size, 2048), 1);
+        // Doesn't overflow for factors enlarging beyond `u32::MAX`.
+        assert_eq!(supported_supersample(u32::MAX, size, 16384), 5);
+    }
+}
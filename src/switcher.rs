@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+use crate::plugin::ImageExportSource;
+
+/// Switches the source an export reads from at runtime, e.g. between the program and preview
+/// cameras of a vision mixer, without respawning the export.
+///
+/// The entity's `Handle<ImageExportSource>` is replaced with the active source before the frame
+/// is extracted. The export node copies every source each frame, so the frame after a switch
+/// is read from the new source without dropping or repeating a frame. Works with any export,
+/// e.g. an [`ImageExportBundle`](crate::ImageExportBundle) or an `NDIExportBundle`. Sources
+/// should share their size and format, as receivers may not expect the frame size to change.
+#[derive(Component, Clone, Debug)]
+pub struct ExportSourceSwitcher {
+    sources: Vec<Handle<ImageExportSource>>,
+    active: usize,
+}
+
+impl ExportSourceSwitcher {
+    /// Creates a switcher reading from the first of `sources`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sources` is empty.
+    pub fn new(sources: Vec<Handle<ImageExportSource>>) -> Self {
+        assert!(!sources.is_empty(), "a source switcher needs a source");
+        Self { sources, active: 0 }
+    }
+
+    pub fn sources(&self) -> &[Handle<ImageExportSource>] {
+        &self.sources
+    }
+
+    /// Index of the source the export reads from.
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn active_source(&self) -> &Handle<ImageExportSource> {
+        &self.sources[self.active]
+    }
+
+    /// Switches to the source at index `active`, starting with the next extracted frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `active` is out of range.
+    pub fn set_active(&mut self, active: usize) {
+        assert!(
+            active < self.sources.len(),
+            "source {active} out of range for {} sources",
+            self.sources.len()
+        );
+        self.active = active;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn switch_export_sources(
+    mut commands: Commands,
+    mut switchers: Query<
+        (
+            Entity,
+            &ExportSourceSwitcher,
+            Option<&mut Handle<ImageExportSource>>,
+        ),
+        Changed<ExportSourceSwitcher>,
+    >,
+) {
+    for (entity, switcher, source_handle) in &mut switchers {
+        let active = switcher.active_source();
+        match source_handle {
+            Some(source_handle) if *source_handle == *active => {}
+            Some(mut source_handle) => {
+                debug!("switching export {entity:?} to source {:?}", active.id());
+                *source_handle = active.clone();
+            }
+            None => {
+                commands.entity(entity).insert(active.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+
+/// CSV file receiving a row per frame a disk export captures or drops, see
+/// [`ImageExportPlugin::with_timing_log`](crate::ImageExportPlugin::with_timing_log).
+#[derive(Resource)]
+pub(crate) struct TimingLog(BufWriter<File>);
+
+impl TimingLog {
+    pub(crate) fn create(path: &Path) -> std::io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            "render_frame,timestamp_ms,entity,export_frame,readback_us,encode_us,dropped"
+        )?;
+        Ok(Self(file))
+    }
+
+    /// Appends a row. Timings are left empty for dropped frames.
+    pub(crate) fn write(
+        &mut self,
+        render_frame: u32,
+        entity: Entity,
+        export_frame: u32,
+        timings: Option<(Duration, Duration)>,
+    ) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let (readback, encode) = timings.map_or((String::new(), String::new()), |(r, e)| {
+            (r.as_micros().to_string(), e.as_micros().to_string())
+        });
+        let dropped = timings.is_none();
+        if let Err(e) = writeln!(
+            self.0,
+            "{render_frame},{timestamp},{entity:?},{export_frame},{readback},{encode},{dropped}"
+        ) {
+            error!("failed to write timing log: {e}");
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if let Err(e) = self.0.flush() {
+            error!("failed to write timing log: {e}");
+        }
+    }
+}
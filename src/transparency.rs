@@ -0,0 +1,121 @@
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig, prelude::*, render::camera::RenderTarget,
+    utils::HashSet,
+};
+
+use crate::plugin::ImageExportSource;
+
+/// Marks an export whose frames are meant to carry transparency, e.g. an NDI overlay.
+///
+/// Cameras rendering into the export's source must clear to a transparent color, otherwise
+/// the exported frames end up with an opaque background. A warning is logged for every
+/// camera that doesn't.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct TransparentExport {
+    /// Sets the clear color of offending cameras to [`Color::NONE`] instead of only warning.
+    pub fix_clear_color: bool,
+}
+
+/// Overrides the clear color of the cameras rendering into the export's source, e.g. magenta
+/// for matte debugging or [`Color::NONE`] for overlays, independently of cameras showing the
+/// scene on screen.
+///
+/// Applied every frame, also to cameras spawned later. A transparent color needs a source image
+/// format with an alpha channel, and a [`TransparentExport`] needs a transparent color; a
+/// warning is logged otherwise.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ExportClearColor(pub Color);
+
+fn is_opaque(config: &ClearColorConfig, clear_color: &ClearColor) -> bool {
+    match config {
+        ClearColorConfig::Default => clear_color.0.a() >= 1.0,
+        ClearColorConfig::Custom(color) => color.a() >= 1.0,
+        ClearColorConfig::None => false,
+    }
+}
+
+pub(crate) fn apply_export_clear_colors(
+    exports: Query<(
+        Entity,
+        &Handle<ImageExportSource>,
+        &ExportClearColor,
+        Has<TransparentExport>,
+    )>,
+    sources: Res<Assets<ImageExportSource>>,
+    images: Res<Assets<Image>>,
+    mut cameras: Query<(&Camera, Option<&mut Camera3d>, Option<&mut Camera2d>)>,
+    mut warned: Local<HashSet<Entity>>,
+) {
+    for (export, source_handle, clear_color, transparent) in &exports {
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        let color = clear_color.0;
+        if !warned.contains(&export) {
+            let has_alpha = images
+                .get(source.image_handle())
+                .is_none_or(|image| image.texture_descriptor.format.components() == 4);
+            if color.a() < 1.0 && !has_alpha {
+                warn!("export {export:?} clears to a transparent color, but its source image has no alpha channel");
+                warned.insert(export);
+            } else if color.a() >= 1.0 && transparent {
+                warn!("transparent export {export:?} clears to the opaque color {color:?}");
+                warned.insert(export);
+            }
+        }
+        for (camera, camera_3d, camera_2d) in &mut cameras {
+            if !matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+            {
+                continue;
+            }
+            let mut config = match (camera_3d, camera_2d) {
+                (Some(camera_3d), _) => camera_3d.map_unchanged(|c| &mut c.clear_color),
+                (_, Some(camera_2d)) => camera_2d.map_unchanged(|c| &mut c.clear_color),
+                _ => continue,
+            };
+            if !matches!(*config, ClearColorConfig::Custom(current) if current == color) {
+                *config = ClearColorConfig::Custom(color);
+            }
+        }
+    }
+}
+
+pub(crate) fn check_transparent_exports(
+    exports: Query<(&Handle<ImageExportSource>, &TransparentExport)>,
+    sources: Res<Assets<ImageExportSource>>,
+    mut cameras: Query<(
+        Entity,
+        &Camera,
+        Option<&mut Camera3d>,
+        Option<&mut Camera2d>,
+    )>,
+    clear_color: Option<Res<ClearColor>>,
+    mut warned: Local<HashSet<Entity>>,
+) {
+    let clear_color = clear_color.map(|c| c.clone()).unwrap_or_default();
+    for (source_handle, transparent) in &exports {
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        for (entity, camera, camera_3d, camera_2d) in &mut cameras {
+            if !matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+            {
+                continue;
+            }
+            let config = match (camera_3d, camera_2d) {
+                (Some(camera_3d), _) => camera_3d.map_unchanged(|c| &mut c.clear_color),
+                (_, Some(camera_2d)) => camera_2d.map_unchanged(|c| &mut c.clear_color),
+                _ => continue,
+            };
+            if !is_opaque(&config, &clear_color) {
+                continue;
+            }
+            if transparent.fix_clear_color {
+                info!("clearing camera {entity:?} to transparent for its transparent export");
+                *config.into_inner() = ClearColorConfig::Custom(Color::NONE);
+            } else if warned.insert(entity) {
+                warn!("camera {entity:?} renders into a transparent export but clears to an opaque color, set its clear color to a transparent one");
+            }
+        }
+    }
+}
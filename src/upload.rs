@@ -0,0 +1,239 @@
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_asset::RenderAssets},
+    utils::HashSet,
+};
+use crossbeam_channel::{bounded, Sender};
+use image::ImageFormat;
+
+use crate::convert::Dithering;
+use crate::disk::{encode_image, into_encodable};
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UploadMethod {
+    #[default]
+    Post,
+    /// Use for S3 (pre-signed) object URLs.
+    Put,
+}
+
+#[derive(Default)]
+struct UploadState {
+    frame: AtomicU32,
+    /// Frames queued or being uploaded.
+    in_flight: Arc<AtomicUsize>,
+    /// Queue of the upload threads, started with the first upload.
+    queue: Mutex<Option<Sender<UploadJob>>>,
+}
+
+/// Uploads every exported frame to an HTTP endpoint, e.g. an S3 bucket.
+///
+/// Frames are encoded and uploaded on `max_in_flight` threads of their own, started once the
+/// first frame is exported, so that slow uploads block neither the render world nor the task
+/// pools.
+#[derive(Component, Clone)]
+pub struct UploadExport {
+    /// `{key}` is replaced with the frame's key. Without it, the key is appended as a path segment.
+    pub endpoint: String,
+    pub method: UploadMethod,
+    /// Value of the `Authorization` header sent with every upload.
+    pub authorization: Option<String>,
+    /// `{frame}` is replaced with the zero-padded frame number and `{extension}` with the
+    /// `extension`.
+    pub key_template: String,
+    /// Determines the image format, e.g. "png".
+    pub extension: String,
+    /// Attempts made after a failed upload before the frame is dropped.
+    pub max_retries: u32,
+    /// Delay before the first retry of a failed upload, doubled for every further one.
+    pub retry_delay: Duration,
+    /// Frames are dropped while this many uploads are still in progress. At least 1.
+    pub max_in_flight: usize,
+    state: Arc<UploadState>,
+}
+
+impl UploadExport {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            method: UploadMethod::default(),
+            authorization: None,
+            key_template: "{frame}.{extension}".into(),
+            extension: "png".into(),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(250),
+            max_in_flight: 8,
+            state: default(),
+        }
+    }
+
+    fn url(&self, frame: u32) -> String {
+        let key = self
+            .key_template
+            .replace("{frame}", &format!("{frame:05}"))
+            .replace("{extension}", &self.extension);
+        if self.endpoint.contains("{key}") {
+            self.endpoint.replace("{key}", &key)
+        } else {
+            format!("{}/{key}", self.endpoint.trim_end_matches('/'))
+        }
+    }
+
+    fn spawn_workers(&self) -> Sender<UploadJob> {
+        let workers = self.max_in_flight.max(1);
+        let (sender, receiver) = bounded::<UploadJob>(workers);
+        for _ in 0..workers {
+            let receiver = receiver.clone();
+            let in_flight = self.state.in_flight.clone();
+            std::thread::spawn(move || {
+                for job in receiver {
+                    job.run();
+                    in_flight.fetch_sub(1, Ordering::AcqRel);
+                }
+            });
+        }
+        sender
+    }
+}
+
+/// A frame queued for an upload thread.
+struct UploadJob {
+    id: AssetId<ImageExportSource>,
+    image: Arc<Image>,
+    extension: String,
+    url: String,
+    method: UploadMethod,
+    authorization: Option<String>,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl UploadJob {
+    fn run(self) {
+        let id = self.id;
+        let bytes =
+            match into_encodable(Cow::Borrowed(&self.image), &self.extension, Dithering::None)
+                .and_then(|(dynamic, _)| encode_image(&dynamic, &self.extension))
+            {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("failed to encode frame of image source {id:?}: {e}");
+                    return;
+                }
+            };
+        let content_type = ImageFormat::from_extension(&self.extension)
+            .map_or("application/octet-stream", |format| format.to_mime_type());
+        let method = match self.method {
+            UploadMethod::Post => "POST",
+            UploadMethod::Put => "PUT",
+        };
+        let url = &self.url;
+        let mut delay = self.retry_delay;
+        for attempt in 0..=self.max_retries {
+            let mut request = ureq::request(method, url).set("Content-Type", content_type);
+            if let Some(authorization) = &self.authorization {
+                request = request.set("Authorization", authorization);
+            }
+            match request.send_bytes(&bytes) {
+                Ok(_) => {
+                    trace!("uploaded frame to {url}");
+                    break;
+                }
+                Err(e) if attempt < self.max_retries => {
+                    warn!("failed to upload frame to {url}, retrying in {delay:?}: {e}");
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(e) => error!("failed to upload frame to {url}: {e}"),
+            }
+        }
+    }
+}
+
+impl ExtractComponent for UploadExport {
+    type Query = (&'static UploadExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (UploadExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some((this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct UploadExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: UploadExport,
+}
+
+pub(crate) fn upload_frames(
+    uploads: Query<(Entity, &UploadExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    let sources = sources.into_inner();
+    let images = images.into_inner();
+
+    for (entity, upload, source_handle) in &uploads {
+        let id = source_handle.id();
+        if !source_is_present(id, sources, images, &mut missing_sources) {
+            continue;
+        }
+        if upload.state.in_flight.load(Ordering::Acquire) >= upload.max_in_flight.max(1) {
+            debug!("dropping frame of image source {id:?}, too many uploads in flight");
+            continue;
+        }
+        let Some(image) = frames.get_export_shared(entity, id) else {
+            continue;
+        };
+
+        let frame = upload.state.frame.fetch_add(1, Ordering::Relaxed);
+        let job = UploadJob {
+            id,
+            image,
+            extension: upload.extension.clone(),
+            url: upload.url(frame),
+            method: upload.method,
+            authorization: upload.authorization.clone(),
+            max_retries: upload.max_retries,
+            retry_delay: upload.retry_delay,
+        };
+        let mut queue = upload.state.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let queue = queue.get_or_insert_with(|| upload.spawn_workers());
+        upload.state.in_flight.fetch_add(1, Ordering::AcqRel);
+        // Only full if `max_in_flight` was raised after the threads were started.
+        if queue.try_send(job).is_err() {
+            upload.state.in_flight.fetch_sub(1, Ordering::AcqRel);
+            debug!("dropping frame of image source {id:?}, the upload queue is full");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_use_the_export_extension() {
+        let mut upload = UploadExport::new("https://example.com/frames/");
+        upload.extension = "jpg".into();
+        assert_eq!(upload.url(42), "https://example.com/frames/00042.jpg");
+
+        upload.endpoint = "https://example.com/{key}?signed".into();
+        upload.key_template = "shot/{frame}.{extension}".into();
+        assert_eq!(upload.url(7), "https://example.com/shot/00007.jpg?signed");
+    }
+}
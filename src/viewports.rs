@@ -0,0 +1,72 @@
+use bevy::{
+    ecs::query::QueryItem,
+    math::URect,
+    prelude::*,
+    render::{camera::RenderTarget, extract_component::ExtractComponent},
+};
+use image::DynamicImage;
+
+use crate::disk::ImageExportSettings;
+use crate::plugin::ImageExportSource;
+
+/// Viewports of the cameras rendering into an export's source, in camera order.
+#[derive(Component, Clone, Debug)]
+pub(crate) struct ExportViewports(Vec<URect>);
+
+impl ExtractComponent for ExportViewports {
+    type Query = &'static Self;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(item.clone())
+    }
+}
+
+impl ExportViewports {
+    /// Crops one image per viewport out of a frame.
+    pub(crate) fn crop(&self, image: &DynamicImage) -> Vec<DynamicImage> {
+        let bounds = URect::new(0, 0, image.width(), image.height());
+        self.0
+            .iter()
+            .map(|viewport| {
+                let rect = viewport.intersect(bounds);
+                image.crop_imm(rect.min.x, rect.min.y, rect.width(), rect.height())
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn update_export_viewports(
+    mut commands: Commands,
+    exports: Query<(Entity, &Handle<ImageExportSource>, &ImageExportSettings)>,
+    sources: Res<Assets<ImageExportSource>>,
+    cameras: Query<&Camera>,
+) {
+    for (entity, source_handle, settings) in &exports {
+        if !settings.split_viewports {
+            continue;
+        }
+        let Some(source) = sources.get(source_handle) else {
+            continue;
+        };
+        let mut cameras: Vec<_> = cameras
+            .iter()
+            .filter(|camera| {
+                matches!(&camera.target, RenderTarget::Image(image) if image == source.image_handle())
+            })
+            .collect();
+        cameras.sort_by_key(|camera| camera.order);
+        let viewports = cameras
+            .iter()
+            .filter_map(|camera| camera.viewport.as_ref())
+            .map(|viewport| {
+                URect::from_corners(
+                    viewport.physical_position,
+                    viewport.physical_position + viewport.physical_size,
+                )
+            })
+            .collect();
+        commands.entity(entity).insert(ExportViewports(viewports));
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    borrow::Cow,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bevy::{
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{extract_component::ExtractComponent, render_asset::RenderAssets},
+    utils::HashSet,
+};
+use futures::{SinkExt, StreamExt};
+use image::ImageFormat;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::convert::Dithering;
+use crate::disk::{encode_image, into_encodable};
+use crate::plugin::{source_is_present, ExportedFrames, ImageExportSource, StreamingSink};
+use crate::resize::ResizeSettings;
+
+/// Pushes every exported frame to the clients connected to a WebSocket server, e.g. to show a
+/// live preview of a headless app in a browser.
+///
+/// Each frame is sent as a text message holding a `data:` URL with the base64 encoded image,
+/// which can be assigned to the `src` of an `<img>` directly. The server is started on a
+/// separate thread once the export is extracted to the render world, which also encodes the
+/// frames. Frames are only read back and encoded while clients are connected and dropped while
+/// the previous one is still being encoded, and a client that falls behind skips the frames it
+/// missed. The export stops if the server can't be started, e.g. because the address is in use.
+/// The server shuts down and releases its address once the export is despawned.
+#[derive(Component, Clone)]
+pub struct WebSocketExport {
+    pub address: SocketAddr,
+    /// Determines the image format, e.g. "png".
+    pub extension: String,
+    /// Frames are scaled by this factor before they are encoded.
+    pub scale: f32,
+    /// Frames buffered for a slow client before the oldest ones are dropped.
+    pub max_queued_frames: usize,
+    server: Arc<Mutex<ServerState>>,
+}
+
+#[derive(Default)]
+enum ServerState {
+    /// The export wasn't extracted yet.
+    #[default]
+    Idle,
+    Running(WebSocketServer),
+    /// The server failed to start.
+    Stopped,
+}
+
+struct WebSocketServer {
+    /// Encoded frames, broadcast to every client.
+    frames: broadcast::Sender<Arc<str>>,
+    /// Frames waiting to be encoded.
+    jobs: mpsc::Sender<EncodeJob>,
+    /// Shuts the server down and releases its address when dropped together with the last
+    /// clone of the export.
+    _shutdown: oneshot::Sender<()>,
+}
+
+/// A frame queued for encoding on the server thread.
+struct EncodeJob {
+    id: AssetId<ImageExportSource>,
+    image: Arc<Image>,
+    extension: String,
+    scale: f32,
+}
+
+impl EncodeJob {
+    /// The `data:` URL of the scaled and encoded frame.
+    fn encode(self) -> Option<Arc<str>> {
+        let id = self.id;
+        let encodable =
+            into_encodable(Cow::Borrowed(&self.image), &self.extension, Dithering::None);
+        let bytes = match encodable.and_then(|(dynamic, srgb)| {
+            let resize = ResizeSettings::scaled(dynamic.width(), dynamic.height(), self.scale);
+            let dynamic = resize.apply(&dynamic, srgb).unwrap_or(dynamic);
+            encode_image(&dynamic, &self.extension)
+        }) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                error!("failed to encode frame of image source {id:?}: {e}");
+                return None;
+            }
+        };
+        let mime_type = ImageFormat::from_extension(&self.extension)
+            .map_or("application/octet-stream", |format| format.to_mime_type());
+        Some(format!("data:{mime_type};base64,{}", STANDARD.encode(bytes)).into())
+    }
+}
+
+impl WebSocketExport {
+    pub fn new(address: impl Into<SocketAddr>) -> Self {
+        Self {
+            address: address.into(),
+            extension: "png".into(),
+            scale: 0.25,
+            max_queued_frames: 2,
+            server: default(),
+        }
+    }
+
+    /// Starts the server unless it was started before, and returns whether clients are
+    /// connected to it.
+    fn has_clients(&self) -> bool {
+        let mut state = self.server.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(*state, ServerState::Idle) {
+            *state = ServerState::Running(self.spawn_server());
+        }
+        let ServerState::Running(server) = &*state else {
+            return false;
+        };
+        // The server thread drops the job receiver when it fails to start, which it logged.
+        if server.jobs.is_closed() {
+            warn!("stopping the WebSocket export on {}", self.address);
+            *state = ServerState::Stopped;
+            return false;
+        }
+        server.frames.receiver_count() > 0
+    }
+
+    fn spawn_server(&self) -> WebSocketServer {
+        let (sender, _) = broadcast::channel(self.max_queued_frames.max(1));
+        let (jobs, mut job_receiver) = mpsc::channel::<EncodeJob>(1);
+        let (shutdown, mut shutdown_receiver) = oneshot::channel::<()>();
+        let frames = sender.clone();
+        let address = self.address;
+        std::thread::spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("failed to start the WebSocket server on {address}: {e}");
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                let listener = match TcpListener::bind(address).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        error!("failed to listen on {address}: {e}");
+                        return;
+                    }
+                };
+                info!("serving frames on ws://{address}");
+                let encoded = frames.clone();
+                tokio::spawn(async move {
+                    while let Some(job) = job_receiver.recv().await {
+                        // Encoding is CPU bound, so it runs on the blocking pool instead of
+                        // stalling the clients.
+                        if let Ok(Some(url)) =
+                            tokio::task::spawn_blocking(move || job.encode()).await
+                        {
+                            // Fails only when the last client disconnected meanwhile.
+                            encoded.send(url).ok();
+                        }
+                    }
+                });
+                loop {
+                    tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, peer)) => {
+                                tokio::spawn(serve_client(stream, peer, frames.subscribe()));
+                            }
+                            Err(e) => warn!("failed to accept WebSocket client: {e}"),
+                        },
+                        _ = &mut shutdown_receiver => break,
+                    }
+                }
+                info!("stopped serving frames on ws://{address}");
+            });
+            // Dropping the runtime disconnects the remaining clients.
+        });
+        WebSocketServer {
+            frames: sender,
+            jobs,
+            _shutdown: shutdown,
+        }
+    }
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    peer: SocketAddr,
+    mut frames: broadcast::Receiver<Arc<str>>,
+) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("WebSocket handshake with {peer} failed: {e}");
+            return;
+        }
+    };
+    debug!("WebSocket client {peer} connected");
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if socket.send(Message::Text(frame.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("WebSocket client {peer} skipped {skipped} frames");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("WebSocket client {peer} disconnected");
+}
+
+impl ExtractComponent for WebSocketExport {
+    type Query = (&'static WebSocketExport, &'static Handle<ImageExportSource>);
+    type Filter = ();
+    type Out = (WebSocketExport, Handle<ImageExportSource>, StreamingSink);
+
+    fn extract_component((this, source_handle): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        // Without clients, the frames aren't read back.
+        this.has_clients()
+            .then(|| (this.clone(), source_handle.clone(), StreamingSink))
+    }
+}
+
+#[derive(Bundle)]
+pub struct WebSocketExportBundle {
+    pub source: Handle<ImageExportSource>,
+    pub export: WebSocketExport,
+}
+
+pub(crate) fn send_to_websockets(
+    websockets: Query<(Entity, &WebSocketExport, &Handle<ImageExportSource>)>,
+    sources: Res<RenderAssets<ImageExportSource>>,
+    images: Res<RenderAssets<Image>>,
+    frames: Res<ExportedFrames>,
+    mut missing_sources: Local<HashSet<AssetId<ImageExportSource>>>,
+) {
+    for (entity, websocket, source_handle) in &websockets {
+        let id = source_handle.id();
+        if !source_is_present(id, &sources, &images, &mut missing_sources) {
+            continue;
+        }
+
+        let server = websocket.server.lock().unwrap_or_else(|e| e.into_inner());
+        let ServerState::Running(server) = &*server else {
+            continue;
+        };
+        // The last client may have disconnected since the export was extracted.
+        if server.frames.receiver_count() == 0 {
+            continue;
+        }
+        let Some(image) = frames.get_export_shared(entity, id) else {
+            continue;
+        };
+        match server.jobs.try_send(EncodeJob {
+            id,
+            image,
+            extension: websocket.extension.clone(),
+            scale: websocket.scale,
+        }) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!(
+                    "dropping frame of image source {id:?}, the WebSocket server on {} is still encoding the previous one",
+                    websocket.address
+                );
+            }
+            // The server failed to start, which stops the export when it's extracted next.
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+}